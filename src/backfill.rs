@@ -0,0 +1,216 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde_json::{json, Map, Value};
+use std::io::Write;
+
+/// Prefix of metric names, same as the gauges exported by the metrics server
+const METRIC_PREFIX: &str = "beacon_network_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// One row per epoch and range
+    Csv,
+    /// One JSON object per epoch and range
+    Jsonl,
+    /// OpenMetrics text with timestamps, for `promtool tsdb create-blocks-from openmetrics`
+    Openmetrics,
+}
+
+/// Values of one range for one epoch
+#[derive(Debug, Clone)]
+pub struct EpochRecord {
+    /// Epoch described by the participation values
+    pub epoch: u64,
+    /// Slot of the state the values are computed from
+    pub slot: u64,
+    /// Unix time of `slot` in seconds
+    pub timestamp: u64,
+    pub range: String,
+    /// Metric names without prefix and values, same names and order for all records
+    pub values: Vec<(&'static str, f64)>,
+}
+
+/// Writes epoch records as they are computed. OpenMetrics groups samples by metric family, so
+/// that format is buffered until `finish`.
+pub struct BackfillWriter<W: Write> {
+    out: W,
+    format: OutputFormat,
+    header_written: bool,
+    buffered: Vec<EpochRecord>,
+}
+
+impl<W: Write> BackfillWriter<W> {
+    pub fn new(out: W, format: OutputFormat) -> Self {
+        BackfillWriter {
+            out,
+            format,
+            header_written: false,
+            buffered: Vec::new(),
+        }
+    }
+
+    pub fn write(&mut self, records: &[EpochRecord]) -> Result<()> {
+        match self.format {
+            OutputFormat::Csv => {
+                for record in records {
+                    if !self.header_written {
+                        let names: Vec<&str> =
+                            record.values.iter().map(|(name, _)| *name).collect();
+                        writeln!(self.out, "epoch,slot,timestamp,range,{}", names.join(","))?;
+                        self.header_written = true;
+                    }
+                    let values: Vec<String> = record
+                        .values
+                        .iter()
+                        .map(|(_, value)| value.to_string())
+                        .collect();
+                    writeln!(
+                        self.out,
+                        "{},{},{},{},{}",
+                        record.epoch,
+                        record.slot,
+                        record.timestamp,
+                        csv_field(&record.range),
+                        values.join(",")
+                    )?;
+                }
+            }
+            OutputFormat::Jsonl => {
+                for record in records {
+                    let mut object = Map::new();
+                    object.insert("epoch".to_owned(), json!(record.epoch));
+                    object.insert("slot".to_owned(), json!(record.slot));
+                    object.insert("timestamp".to_owned(), json!(record.timestamp));
+                    object.insert("range".to_owned(), json!(record.range));
+                    for (name, value) in record.values.iter() {
+                        // NaN, i.e. ratios of empty groups, is not valid JSON and becomes null
+                        object.insert(name.to_string(), json!(value));
+                    }
+                    writeln!(self.out, "{}", Value::Object(object))?;
+                }
+            }
+            OutputFormat::Openmetrics => self.buffered.extend_from_slice(records),
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        if self.format == OutputFormat::Openmetrics {
+            let names: Vec<&str> = self
+                .buffered
+                .first()
+                .map(|record| record.values.iter().map(|(name, _)| *name).collect())
+                .unwrap_or_default();
+            for (i, name) in names.iter().enumerate() {
+                writeln!(self.out, "# TYPE {METRIC_PREFIX}{name} gauge")?;
+                for record in self.buffered.iter() {
+                    // Ratios of groups with no eligible validators have no sample, as in the
+                    // metrics server
+                    if record.values[i].1.is_nan() {
+                        continue;
+                    }
+                    writeln!(
+                        self.out,
+                        "{}{}{{range=\"{}\"}} {} {}",
+                        METRIC_PREFIX,
+                        name,
+                        label_value(&record.range),
+                        record.values[i].1,
+                        record.timestamp
+                    )?;
+                }
+            }
+            writeln!(self.out, "# EOF")?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+fn label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records() -> Vec<EpochRecord> {
+        vec![
+            EpochRecord {
+                epoch: 10,
+                slot: 352,
+                timestamp: 1000,
+                range: "a".to_owned(),
+                values: vec![("target_participation", 0.5), ("balance_sum_gwei", 64.0)],
+            },
+            EpochRecord {
+                epoch: 10,
+                slot: 352,
+                timestamp: 1000,
+                range: "b, \"c\"".to_owned(),
+                values: vec![
+                    ("target_participation", f64::NAN),
+                    ("balance_sum_gwei", 0.0),
+                ],
+            },
+        ]
+    }
+
+    fn write_all(format: OutputFormat) -> String {
+        let mut out = Vec::new();
+        let mut writer = BackfillWriter::new(&mut out, format);
+        let records = records();
+        writer.write(&records[..1]).unwrap();
+        writer.write(&records[1..]).unwrap();
+        writer.finish().unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn backfill_writer_csv() {
+        assert_eq!(
+            write_all(OutputFormat::Csv),
+            "epoch,slot,timestamp,range,target_participation,balance_sum_gwei
+10,352,1000,a,0.5,64
+10,352,1000,\"b, \"\"c\"\"\",NaN,0
+"
+        );
+    }
+
+    #[test]
+    fn backfill_writer_jsonl() {
+        assert_eq!(
+            write_all(OutputFormat::Jsonl),
+            r#"{"balance_sum_gwei":64.0,"epoch":10,"range":"a","slot":352,"target_participation":0.5,"timestamp":1000}
+{"balance_sum_gwei":0.0,"epoch":10,"range":"b, \"c\"","slot":352,"target_participation":null,"timestamp":1000}
+"#
+        );
+    }
+
+    #[test]
+    fn backfill_writer_openmetrics() {
+        assert_eq!(
+            write_all(OutputFormat::Openmetrics),
+            r#"# TYPE beacon_network_target_participation gauge
+beacon_network_target_participation{range="a"} 0.5 1000
+# TYPE beacon_network_balance_sum_gwei gauge
+beacon_network_balance_sum_gwei{range="a"} 64 1000
+beacon_network_balance_sum_gwei{range="b, \"c\""} 0 1000
+# EOF
+"#
+        );
+    }
+}
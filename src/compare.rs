@@ -0,0 +1,59 @@
+use crate::ssz_state::StatePartial;
+
+/// Disagreements of a node's state with the reference state at the same slot
+#[derive(Debug, Default, PartialEq)]
+pub struct StateDiff {
+    /// Slot of the node's state, differs from the reference if the node served another state
+    pub slot: u64,
+    /// Count of validator indexes with different flags, including indexes only in one state
+    pub previous_epoch_participation: usize,
+    pub current_epoch_participation: usize,
+    pub inactivity_scores: usize,
+    pub balances: usize,
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.previous_epoch_participation == 0
+            && self.current_epoch_participation == 0
+            && self.inactivity_scores == 0
+            && self.balances == 0
+    }
+}
+
+/// Count of indexes where `a` and `b` differ. Items beyond the shorter list count as different.
+fn count_diff<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    let common_diff = a.iter().zip(b.iter()).filter(|(a, b)| a != b).count();
+    common_diff + a.len().abs_diff(b.len())
+}
+
+/// Compare the fields of `state` that clients compute independently during epoch processing
+pub fn compare_states(reference: &StatePartial, state: &StatePartial) -> StateDiff {
+    StateDiff {
+        slot: state.slot,
+        previous_epoch_participation: count_diff(
+            &reference.previous_epoch_participation,
+            &state.previous_epoch_participation,
+        ),
+        current_epoch_participation: count_diff(
+            &reference.current_epoch_participation,
+            &state.current_epoch_participation,
+        ),
+        inactivity_scores: count_diff(&reference.inactivity_scores, &state.inactivity_scores),
+        balances: count_diff(&reference.balances, &state.balances),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_diff_test() {
+        assert_eq!(count_diff::<u8>(&[], &[]), 0);
+        assert_eq!(count_diff(&[1u8, 2, 3], &[1, 2, 3]), 0);
+        assert_eq!(count_diff(&[1u8, 2, 3], &[1, 0, 3]), 1);
+        assert_eq!(count_diff(&[1u8, 2, 3], &[1, 0]), 2);
+        assert_eq!(count_diff(&[1u8], &[0, 7, 7]), 3);
+    }
+}
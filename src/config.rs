@@ -1,29 +1,312 @@
-use anyhow::{Error, Result};
+use crate::util::resolve_path_or_url;
+use anyhow::{anyhow, Context, Error, Result};
 use reqwest::header::HeaderMap;
 use serde::Deserialize;
+use std::{collections::HashMap, str::FromStr};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ForkName {
+    Phase0,
+    Altair,
+    Bellatrix,
+    Capella,
+    Deneb,
+    Electra,
+}
+
+impl ForkName {
+    pub const ALL: [ForkName; 6] = [
+        ForkName::Phase0,
+        ForkName::Altair,
+        ForkName::Bellatrix,
+        ForkName::Capella,
+        ForkName::Deneb,
+        ForkName::Electra,
+    ];
+
+    /// Lowercase name as in the `Eth-Consensus-Version` header
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ForkName::Phase0 => "phase0",
+            ForkName::Altair => "altair",
+            ForkName::Bellatrix => "bellatrix",
+            ForkName::Capella => "capella",
+            ForkName::Deneb => "deneb",
+            ForkName::Electra => "electra",
+        }
+    }
+}
+
+impl FromStr for ForkName {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        ForkName::ALL
+            .into_iter()
+            .find(|fork| fork.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| anyhow!("Unknown fork name: {}", s))
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct ConfigSpec {
     pub seconds_per_slot: u64,
     pub slots_per_epoch: u64,
     pub slots_per_historical_root: usize,
     pub epochs_per_historical_vector: usize,
     pub epochs_per_slashings_vector: usize,
+    pub sync_committee_size: usize,
+    pub epochs_per_sync_committee_period: u64,
+    pub max_committees_per_slot: u64,
+    pub target_committee_size: u64,
+    pub shuffle_round_count: u8,
+    pub min_seed_lookahead: u64,
+    /// Fork version of each fork in the order of `ForkName::ALL`, None if unknown to the node
+    pub fork_versions: [Option<[u8; 4]>; ForkName::ALL.len()],
 }
 
-#[allow(non_snake_case)]
-#[derive(Deserialize)]
-struct ConfigSpecResponseData {
-    SECONDS_PER_SLOT: String,
-    SLOTS_PER_EPOCH: String,
-    SLOTS_PER_HISTORICAL_ROOT: String,
-    EPOCHS_PER_HISTORICAL_VECTOR: String,
-    EPOCHS_PER_SLASHINGS_VECTOR: String,
+/// Config keys of the fork versions in the order of `ForkName::ALL`
+const FORK_VERSION_KEYS: [&str; ForkName::ALL.len()] = [
+    "GENESIS_FORK_VERSION",
+    "ALTAIR_FORK_VERSION",
+    "BELLATRIX_FORK_VERSION",
+    "CAPELLA_FORK_VERSION",
+    "DENEB_FORK_VERSION",
+    "ELECTRA_FORK_VERSION",
+];
+
+impl ConfigSpec {
+    pub fn fork_of_version(&self, version: &[u8; 4]) -> Option<ForkName> {
+        ForkName::ALL
+            .into_iter()
+            .zip(self.fork_versions.iter())
+            .find(|(_, fork_version)| fork_version.as_ref() == Some(version))
+            .map(|(fork, _)| fork)
+    }
+
+    /// Values used to decode states and compute participation, with their config keys
+    fn preset_values(&self) -> [(&'static str, u64); 11] {
+        [
+            ("SECONDS_PER_SLOT", self.seconds_per_slot),
+            ("SLOTS_PER_EPOCH", self.slots_per_epoch),
+            (
+                "SLOTS_PER_HISTORICAL_ROOT",
+                self.slots_per_historical_root as u64,
+            ),
+            (
+                "EPOCHS_PER_HISTORICAL_VECTOR",
+                self.epochs_per_historical_vector as u64,
+            ),
+            (
+                "EPOCHS_PER_SLASHINGS_VECTOR",
+                self.epochs_per_slashings_vector as u64,
+            ),
+            ("SYNC_COMMITTEE_SIZE", self.sync_committee_size as u64),
+            (
+                "EPOCHS_PER_SYNC_COMMITTEE_PERIOD",
+                self.epochs_per_sync_committee_period,
+            ),
+            ("MAX_COMMITTEES_PER_SLOT", self.max_committees_per_slot),
+            ("TARGET_COMMITTEE_SIZE", self.target_committee_size),
+            ("SHUFFLE_ROUND_COUNT", self.shuffle_round_count as u64),
+            ("MIN_SEED_LOOKAHEAD", self.min_seed_lookahead),
+        ]
+    }
+
+    /// Build from the `KEY: value` entries of the config spec endpoint or the config and preset
+    /// YAML files. Fork versions missing from `entries` are None.
+    pub fn from_entries(entries: &HashMap<String, String>) -> Result<ConfigSpec> {
+        let mut fork_versions = [None; ForkName::ALL.len()];
+        for (fork_version, key) in fork_versions.iter_mut().zip(FORK_VERSION_KEYS) {
+            if let Some(version_str) = entries.get(key) {
+                *fork_version = Some(parse_fork_version(version_str, key)?);
+            }
+        }
+        Ok(ConfigSpec {
+            seconds_per_slot: parse_entry(entries, "SECONDS_PER_SLOT")?,
+            slots_per_epoch: parse_entry(entries, "SLOTS_PER_EPOCH")?,
+            slots_per_historical_root: parse_entry(entries, "SLOTS_PER_HISTORICAL_ROOT")?,
+            epochs_per_historical_vector: parse_entry(entries, "EPOCHS_PER_HISTORICAL_VECTOR")?,
+            epochs_per_slashings_vector: parse_entry(entries, "EPOCHS_PER_SLASHINGS_VECTOR")?,
+            sync_committee_size: parse_entry(entries, "SYNC_COMMITTEE_SIZE")?,
+            epochs_per_sync_committee_period: parse_entry(
+                entries,
+                "EPOCHS_PER_SYNC_COMMITTEE_PERIOD",
+            )?,
+            max_committees_per_slot: parse_entry(entries, "MAX_COMMITTEES_PER_SLOT")?,
+            target_committee_size: parse_entry(entries, "TARGET_COMMITTEE_SIZE")?,
+            shuffle_round_count: parse_entry(entries, "SHUFFLE_ROUND_COUNT")?,
+            min_seed_lookahead: parse_entry(entries, "MIN_SEED_LOOKAHEAD")?,
+            fork_versions,
+        })
+    }
+}
+
+/// Fork versions of a network, whose first byte is the fork index
+const fn fork_versions(network_bytes: [u8; 3]) -> [Option<[u8; 4]>; ForkName::ALL.len()] {
+    let [a, b, c] = network_bytes;
+    [
+        Some([0, a, b, c]),
+        Some([1, a, b, c]),
+        Some([2, a, b, c]),
+        Some([3, a, b, c]),
+        Some([4, a, b, c]),
+        Some([5, a, b, c]),
+    ]
+}
+
+/// Fork version as written in config files: `0x01017000`
+const fn version(version: u32) -> Option<[u8; 4]> {
+    Some(version.to_be_bytes())
+}
+
+const CONFIG_MAINNET: ConfigSpec = ConfigSpec {
+    seconds_per_slot: 12,
+    slots_per_epoch: 32,
+    slots_per_historical_root: 8192,
+    epochs_per_historical_vector: 65536,
+    epochs_per_slashings_vector: 8192,
+    sync_committee_size: 512,
+    epochs_per_sync_committee_period: 256,
+    max_committees_per_slot: 64,
+    target_committee_size: 128,
+    shuffle_round_count: 90,
+    min_seed_lookahead: 1,
+    fork_versions: fork_versions([0, 0, 0]),
+};
+
+const CONFIG_GNOSIS: ConfigSpec = ConfigSpec {
+    seconds_per_slot: 5,
+    slots_per_epoch: 16,
+    epochs_per_sync_committee_period: 512,
+    fork_versions: fork_versions([0, 0, 0x64]),
+    ..CONFIG_MAINNET
+};
+
+const CONFIG_HOLESKY: ConfigSpec = ConfigSpec {
+    fork_versions: [
+        version(0x01017000),
+        version(0x02017000),
+        version(0x03017000),
+        version(0x04017000),
+        version(0x05017000),
+        version(0x06017000),
+    ],
+    ..CONFIG_MAINNET
+};
+
+const CONFIG_SEPOLIA: ConfigSpec = ConfigSpec {
+    fork_versions: [
+        version(0x90000069),
+        version(0x90000070),
+        version(0x90000071),
+        version(0x90000072),
+        version(0x90000073),
+        version(0x90000074),
+    ],
+    ..CONFIG_MAINNET
+};
+
+const CONFIG_HOODI: ConfigSpec = ConfigSpec {
+    fork_versions: [
+        version(0x10000910),
+        version(0x20000910),
+        version(0x30000910),
+        version(0x40000910),
+        version(0x50000910),
+        version(0x60000910),
+    ],
+    ..CONFIG_MAINNET
+};
+
+const CONFIG_CHIADO: ConfigSpec = ConfigSpec {
+    fork_versions: fork_versions([0, 0, 0x6f]),
+    ..CONFIG_GNOSIS
+};
+
+const CONFIG_MINIMAL: ConfigSpec = ConfigSpec {
+    seconds_per_slot: 6,
+    slots_per_epoch: 8,
+    slots_per_historical_root: 64,
+    epochs_per_historical_vector: 64,
+    epochs_per_slashings_vector: 64,
+    sync_committee_size: 32,
+    epochs_per_sync_committee_period: 8,
+    max_committees_per_slot: 4,
+    target_committee_size: 4,
+    shuffle_round_count: 10,
+    min_seed_lookahead: 1,
+    fork_versions: fork_versions([0, 0, 1]),
+};
+
+pub const NETWORKS: [&str; 7] = [
+    "mainnet", "gnosis", "holesky", "sepolia", "hoodi", "chiado", "minimal",
+];
+
+/// Config and genesis of a known network by name, to run without the config endpoints of a
+/// beacon node. Genesis is None for the minimal preset, which has no canonical chain.
+pub fn builtin_network(name: &str) -> Result<(ConfigSpec, Option<Genesis>)> {
+    let (config, genesis_time) = match name.to_lowercase().as_str() {
+        "mainnet" => (CONFIG_MAINNET, Some(1606824023)),
+        "gnosis" => (CONFIG_GNOSIS, Some(1638993340)),
+        "holesky" => (CONFIG_HOLESKY, Some(1695902400)),
+        "sepolia" => (CONFIG_SEPOLIA, Some(1655733600)),
+        "hoodi" => (CONFIG_HOODI, Some(1742213400)),
+        "chiado" => (CONFIG_CHIADO, Some(1665396300)),
+        "minimal" => (CONFIG_MINIMAL, None),
+        _ => {
+            return Err(anyhow!(
+                "Unknown network {}, must be one of {}",
+                name,
+                NETWORKS.join(", ")
+            ))
+        }
+    };
+    Ok((
+        config,
+        genesis_time.map(|genesis_time| Genesis { genesis_time }),
+    ))
+}
+
+/// Check a config fetched from a beacon node against the built-in network with the same genesis
+/// fork version. States decoded with the wrong preset are garbage, so a node serving a config
+/// that disagrees with its network is rejected. Returns the name of the matched network.
+pub fn check_builtin_preset(config: &ConfigSpec) -> Result<Option<&'static str>> {
+    // The minimal preset has no canonical chain, devnets share its fork versions
+    for name in NETWORKS.into_iter().filter(|name| *name != "minimal") {
+        let builtin = builtin_config(name)?;
+        if builtin.fork_versions[0].is_none() || builtin.fork_versions[0] != config.fork_versions[0]
+        {
+            continue;
+        }
+        let mismatches: Vec<String> = builtin
+            .preset_values()
+            .into_iter()
+            .zip(config.preset_values())
+            .filter(|((_, expected), (_, value))| expected != value)
+            .map(|((key, expected), (_, value))| format!("{} {} != {}", key, value, expected))
+            .collect();
+        if !mismatches.is_empty() {
+            return Err(anyhow!(
+                "config of genesis fork version of {} does not match its preset: {}",
+                name,
+                mismatches.join(", ")
+            ));
+        }
+        return Ok(Some(name));
+    }
+    Ok(None)
+}
+
+/// Config of a known network by name, to decode states without a beacon node
+pub fn builtin_config(name: &str) -> Result<ConfigSpec> {
+    Ok(builtin_network(name)?.0)
 }
 
 #[derive(Deserialize)]
 struct ConfigSpecResponse {
-    data: ConfigSpecResponseData,
+    /// Values are strings, except for some nested entries like `BLOB_SCHEDULE` which are ignored
+    data: HashMap<String, serde_json::Value>,
 }
 
 pub async fn fetch_config(url: &str, extra_headers: &HeaderMap) -> Result<ConfigSpec> {
@@ -33,29 +316,77 @@ pub async fn fetch_config(url: &str, extra_headers: &HeaderMap) -> Result<Config
         .send()
         .await?;
     let data: ConfigSpecResponse = response.json().await?;
-    Ok(ConfigSpec {
-        seconds_per_slot: parse_usize(&data.data.SECONDS_PER_SLOT, "SECONDS_PER_SLOT")? as u64,
-        slots_per_epoch: parse_usize(&data.data.SLOTS_PER_EPOCH, "SLOTS_PER_EPOCH")? as u64,
-        slots_per_historical_root: parse_usize(
-            &data.data.SLOTS_PER_HISTORICAL_ROOT,
-            "SLOTS_PER_HISTORICAL_ROOT",
-        )?,
-        epochs_per_historical_vector: parse_usize(
-            &data.data.EPOCHS_PER_HISTORICAL_VECTOR,
-            "EPOCHS_PER_HISTORICAL_VECTOR",
-        )?,
-        epochs_per_slashings_vector: parse_usize(
-            &data.data.EPOCHS_PER_SLASHINGS_VECTOR,
-            "EPOCHS_PER_SLASHINGS_VECTOR",
-        )?,
-    })
+    let entries = data
+        .data
+        .into_iter()
+        .filter_map(|(key, value)| match value {
+            serde_json::Value::String(value) => Some((key, value)),
+            _ => None,
+        })
+        .collect();
+    ConfigSpec::from_entries(&entries)
+}
+
+/// Parse the top level `KEY: value` entries of a consensus-specs style config or preset YAML.
+/// Nested entries like `BLOB_SCHEDULE` are skipped.
+pub fn parse_config_yaml(yaml: &str) -> Result<HashMap<String, String>> {
+    let mapping: serde_yaml::Mapping = serde_yaml::from_str(yaml)?;
+    let mut entries = HashMap::new();
+    for (key, value) in mapping {
+        let Some(key) = key.as_str() else {
+            continue;
+        };
+        let value = match value {
+            serde_yaml::Value::String(value) => value,
+            // Unquoted hex fork versions are YAML integers, format them back as 4 bytes
+            serde_yaml::Value::Number(number) if FORK_VERSION_KEYS.contains(&key) => {
+                match number.as_u64().and_then(|n| u32::try_from(n).ok()) {
+                    Some(version) => format!("0x{:08x}", version),
+                    None => return Err(anyhow!("{} not 4 bytes: {}", key, number)),
+                }
+            }
+            serde_yaml::Value::Number(number) => number.to_string(),
+            serde_yaml::Value::Bool(value) => value.to_string(),
+            _ => continue,
+        };
+        entries.insert(key.to_owned(), value);
+    }
+    Ok(entries)
 }
 
-fn parse_usize(usize_str: &str, name: &'static str) -> Result<usize> {
-    usize_str.parse().map_err(|e| Error::new(e).context(name))
+/// Load a consensus-specs style `config.yaml` and the preset YAML files of its `PRESET_BASE`,
+/// each from a local path or URL. Entries of `config_path` take precedence over the presets.
+pub async fn load_config_yaml(config_path: &str, preset_paths: &[String]) -> Result<ConfigSpec> {
+    let mut entries = HashMap::new();
+    for path in preset_paths.iter().map(String::as_str).chain([config_path]) {
+        let yaml = resolve_path_or_url(path)
+            .await
+            .with_context(|| format!("loading {}", path))?;
+        entries.extend(parse_config_yaml(&yaml).with_context(|| format!("parsing {}", path))?);
+    }
+    ConfigSpec::from_entries(&entries)
 }
 
-#[derive(Debug, Deserialize)]
+fn parse_entry<T: FromStr>(entries: &HashMap<String, String>, key: &'static str) -> Result<T>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    entries
+        .get(key)
+        .ok_or_else(|| anyhow!("config missing {}", key))?
+        .parse()
+        .map_err(|e| Error::new(e).context(key))
+}
+
+fn parse_fork_version(version_str: &str, name: &'static str) -> Result<[u8; 4]> {
+    let bytes = hex::decode(version_str.trim_start_matches("0x"))
+        .map_err(|e| Error::new(e).context(name))?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow!("{} not 4 bytes: {}", name, version_str))
+}
+
+#[derive(Debug, Clone, Deserialize)]
 pub struct Genesis {
     pub genesis_time: u64,
 }
@@ -81,3 +412,123 @@ pub async fn fetch_genesis(url: &str, extra_headers: &HeaderMap) -> Result<Genes
         genesis_time: data.data.genesis_time.parse()?,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_config_test() {
+        let config = builtin_config("Gnosis").unwrap();
+        assert_eq!(config.slots_per_epoch, 16);
+        assert_eq!(
+            config.fork_of_version(&[0x03, 0, 0, 0x64]),
+            Some(ForkName::Capella)
+        );
+        assert_eq!(
+            builtin_config("mainnet")
+                .unwrap()
+                .fork_of_version(&[0x05, 0, 0, 0]),
+            Some(ForkName::Electra)
+        );
+        assert!(builtin_config("unknown").is_err());
+    }
+
+    #[test]
+    fn builtin_network_test() {
+        for name in NETWORKS {
+            let (config, genesis) = builtin_network(name).unwrap();
+            assert!(config.fork_versions.iter().all(Option::is_some), "{}", name);
+            assert_eq!(genesis.is_none(), name == "minimal");
+        }
+        let (config, genesis) = builtin_network("holesky").unwrap();
+        assert_eq!(genesis.unwrap().genesis_time, 1695902400);
+        assert_eq!(
+            config.fork_of_version(&[0x06, 0x01, 0x70, 0x00]),
+            Some(ForkName::Electra)
+        );
+    }
+
+    #[test]
+    fn check_builtin_preset_test() {
+        assert_eq!(
+            check_builtin_preset(&builtin_config("gnosis").unwrap()).unwrap(),
+            Some("gnosis")
+        );
+        assert_eq!(
+            check_builtin_preset(&builtin_config("minimal").unwrap()).unwrap(),
+            None
+        );
+
+        let wrong_preset = ConfigSpec {
+            slots_per_historical_root: 64,
+            ..builtin_config("mainnet").unwrap()
+        };
+        let err = check_builtin_preset(&wrong_preset).unwrap_err().to_string();
+        assert!(
+            err.contains("mainnet") && err.contains("SLOTS_PER_HISTORICAL_ROOT 64 != 8192"),
+            "{}",
+            err
+        );
+
+        let devnet = ConfigSpec {
+            fork_versions: [Some([0x10, 0, 0, 0x38]); ForkName::ALL.len()],
+            ..wrong_preset
+        };
+        assert_eq!(check_builtin_preset(&devnet).unwrap(), None);
+    }
+
+    #[test]
+    fn config_yaml_test() {
+        let preset = "\
+# Mainnet preset - Phase0
+SLOTS_PER_EPOCH: 32
+SLOTS_PER_HISTORICAL_ROOT: 8192
+EPOCHS_PER_HISTORICAL_VECTOR: 65536
+EPOCHS_PER_SLASHINGS_VECTOR: 8192
+MAX_COMMITTEES_PER_SLOT: 64
+TARGET_COMMITTEE_SIZE: 128
+SHUFFLE_ROUND_COUNT: 90
+MIN_SEED_LOOKAHEAD: 1
+SYNC_COMMITTEE_SIZE: 512
+EPOCHS_PER_SYNC_COMMITTEE_PERIOD: 256
+";
+        let config = "\
+PRESET_BASE: 'mainnet'
+CONFIG_NAME: 'devnet # not a comment' # comment
+SECONDS_PER_SLOT: 6
+GENESIS_FORK_VERSION: 0x00000038
+ALTAIR_FORK_VERSION: '0x20000038'
+BELLATRIX_FORK_VERSION: null
+TERMINAL_BLOCK_HASH: >-
+  0x0000000000000000000000000000000000000000000000000000000000000000
+BLOB_SCHEDULE:
+  - EPOCH: 100
+    MAX_BLOBS_PER_BLOCK: 12
+";
+        let mut entries = parse_config_yaml(preset).unwrap();
+        entries.extend(parse_config_yaml(config).unwrap());
+        assert_eq!(entries["CONFIG_NAME"], "devnet # not a comment");
+        assert_eq!(entries["GENESIS_FORK_VERSION"], "0x00000038");
+        assert_eq!(
+            entries["TERMINAL_BLOCK_HASH"],
+            format!("0x{}", "0".repeat(64))
+        );
+        assert!(!entries.contains_key("BLOB_SCHEDULE"));
+        assert!(!entries.contains_key("EPOCH"));
+
+        let config = ConfigSpec::from_entries(&entries).unwrap();
+        assert_eq!(config.seconds_per_slot, 6);
+        assert_eq!(config.shuffle_round_count, 90);
+        assert_eq!(
+            config.fork_of_version(&[0x20, 0, 0, 0x38]),
+            Some(ForkName::Altair)
+        );
+        assert_eq!(config.fork_versions[0], Some([0, 0, 0, 0x38]));
+        assert_eq!(config.fork_versions[2], None);
+        assert!(parse_config_yaml("SLOTS_PER_EPOCH: [32").is_err());
+
+        entries.remove("SLOTS_PER_EPOCH");
+        assert!(ConfigSpec::from_entries(&entries).is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use anyhow::{anyhow, Result};
+use log::debug;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
+use std::str::FromStr;
+use url::Url;
+
+/// Beacon node HTTP API with the headers to send on each request
+#[derive(Debug, Clone)]
+pub struct BeaconEndpoint {
+    pub url: String,
+    pub headers: HeaderMap,
+}
+
+impl BeaconEndpoint {
+    /// URL without credentials, safe to log and use as metric label
+    pub fn label(&self) -> String {
+        match Url::parse(&self.url) {
+            Ok(mut url) => {
+                let _ = url.set_username("");
+                let _ = url.set_password(None);
+                url.as_str().trim_end_matches('/').to_owned()
+            }
+            Err(_) => self.url.clone(),
+        }
+    }
+}
+
+/// Parse a header with the same format as curl: `Authorization: Bearer {token}`
+pub fn parse_header(header_str: &str) -> Result<(HeaderName, HeaderValue)> {
+    let (name, value) = header_str
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Invalid header: {}", header_str))?;
+    Ok((HeaderName::from_str(name.trim())?, value.trim().parse()?))
+}
+
+/// Build the endpoints of `urls`, in priority order. `headers` are sent to all endpoints, and
+/// each of `url_headers` only to the endpoint of its URL, with format `{url}={header}`.
+pub fn parse_endpoints(
+    urls: &[String],
+    headers: &[String],
+    url_headers: &[String],
+) -> Result<Vec<BeaconEndpoint>> {
+    let mut common_headers = HeaderMap::new();
+    for header_str in headers {
+        let (name, value) = parse_header(header_str)?;
+        common_headers.insert(name, value);
+    }
+
+    let mut endpoints: Vec<BeaconEndpoint> = urls
+        .iter()
+        .map(|url| BeaconEndpoint {
+            url: url.trim_end_matches('/').to_owned(),
+            headers: common_headers.clone(),
+        })
+        .collect();
+
+    for url_header_str in url_headers {
+        // Match against the URLs given, since both URLs and header values may contain '='
+        let (endpoint, header_str) = urls
+            .iter()
+            .zip(endpoints.iter_mut())
+            .find_map(|(url, endpoint)| {
+                url_header_str
+                    .strip_prefix(url.as_str())
+                    .and_then(|rest| rest.strip_prefix('='))
+                    .map(|header_str| (endpoint, header_str))
+            })
+            .ok_or_else(|| {
+                anyhow!(
+                    "Invalid URL header, must start with one of the beacon URLs followed by '=': {}",
+                    url_header_str
+                )
+            })?;
+        let (name, value) = parse_header(header_str)?;
+        endpoint.headers.insert(name, value);
+    }
+
+    Ok(endpoints)
+}
+
+#[derive(Deserialize)]
+struct SyncingResponse {
+    data: SyncingData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SyncingData {
+    pub is_syncing: bool,
+    /// Not returned by older nodes
+    #[serde(default)]
+    pub is_optimistic: bool,
+}
+
+impl SyncingData {
+    /// A node serving state of a chain it has not fully verified may report wrong participation
+    pub fn is_ready(&self) -> bool {
+        !self.is_syncing && !self.is_optimistic
+    }
+}
+
+pub async fn fetch_syncing(endpoint: &BeaconEndpoint) -> Result<SyncingData> {
+    let res = reqwest::Client::new()
+        .get(format!("{}/eth/v1/node/syncing", endpoint.url))
+        .headers(endpoint.headers.clone())
+        .send()
+        .await?;
+    if !res.status().is_success() {
+        return Err(anyhow!(
+            "getSyncingStatus returned not success code {}",
+            res.status().as_str()
+        ));
+    }
+    let syncing: SyncingResponse = res.json().await?;
+    debug!("{} syncing status {:?}", endpoint.label(), syncing.data);
+    Ok(syncing.data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_endpoints_test() {
+        let urls = vec![
+            "http://a:4000/".to_owned(),
+            "http://user:pass@b:5052".to_owned(),
+        ];
+        let endpoints = parse_endpoints(
+            &urls,
+            &["X-Common: 1".to_owned()],
+            &["http://user:pass@b:5052=Authorization: Bearer abc==".to_owned()],
+        )
+        .unwrap();
+
+        assert_eq!(endpoints[0].url, "http://a:4000");
+        assert_eq!(endpoints[0].label(), "http://a:4000");
+        assert_eq!(endpoints[0].headers.len(), 1);
+        assert_eq!(endpoints[1].label(), "http://b:5052");
+        assert_eq!(endpoints[1].headers["x-common"], "1");
+        assert_eq!(endpoints[1].headers["authorization"], "Bearer abc==");
+
+        assert!(parse_endpoints(&urls, &[], &["http://c=X: 1".to_owned()]).is_err());
+        assert!(parse_endpoints(&urls, &["no-colon".to_owned()], &[]).is_err());
+    }
+}
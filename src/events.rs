@@ -0,0 +1,303 @@
+use crate::endpoints::{fetch_syncing, BeaconEndpoint};
+use anyhow::{anyhow, Result};
+use log::{debug, error, info};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::sync::mpsc::Sender;
+use tokio::time;
+
+pub const EVENT_TOPICS: &str = "head,finalized_checkpoint,chain_reorg";
+
+#[derive(Debug, PartialEq)]
+pub enum BeaconEvent {
+    Head { slot: u64 },
+    FinalizedCheckpoint { epoch: u64 },
+    ChainReorg { slot: u64, depth: u64 },
+}
+
+#[derive(Deserialize)]
+struct HeadEvent {
+    slot: String,
+}
+
+#[derive(Deserialize)]
+struct FinalizedCheckpointEvent {
+    epoch: String,
+}
+
+#[derive(Deserialize)]
+struct ChainReorgEvent {
+    slot: String,
+    depth: String,
+}
+
+/// Parse the data of an event of `EVENT_TOPICS`. Returns None for other events
+fn parse_event(event: &str, data: &str) -> Result<Option<BeaconEvent>> {
+    Ok(Some(match event {
+        "head" => {
+            let head: HeadEvent = serde_json::from_str(data)?;
+            BeaconEvent::Head {
+                slot: head.slot.parse()?,
+            }
+        }
+        "finalized_checkpoint" => {
+            let checkpoint: FinalizedCheckpointEvent = serde_json::from_str(data)?;
+            BeaconEvent::FinalizedCheckpoint {
+                epoch: checkpoint.epoch.parse()?,
+            }
+        }
+        "chain_reorg" => {
+            let reorg: ChainReorgEvent = serde_json::from_str(data)?;
+            BeaconEvent::ChainReorg {
+                slot: reorg.slot.parse()?,
+                depth: reorg.depth.parse()?,
+            }
+        }
+        _ => return Ok(None),
+    }))
+}
+
+/// A reorg replacing blocks before the start of the epoch of its new head changes the epoch
+/// boundary state
+pub fn reorg_crosses_epoch(slot: u64, depth: u64, slots_per_epoch: u64) -> bool {
+    slot.saturating_sub(depth) / slots_per_epoch < slot / slots_per_epoch
+}
+
+/// Incremental parser of a `text/event-stream` body, which may split events across chunks
+#[derive(Default)]
+struct SseParser {
+    /// Bytes of an incomplete line
+    line: Vec<u8>,
+    event: String,
+    data: String,
+}
+
+impl SseParser {
+    /// Feed a chunk of the stream, returns the (event, data) pairs completed by it
+    fn push(&mut self, chunk: &[u8]) -> Vec<(String, String)> {
+        let mut events = Vec::new();
+        for byte in chunk {
+            if *byte != b'\n' {
+                self.line.push(*byte);
+                continue;
+            }
+            let line = String::from_utf8_lossy(&self.line)
+                .trim_end_matches('\r')
+                .to_owned();
+            self.line.clear();
+
+            if line.is_empty() {
+                // Blank line dispatches the event
+                if !self.data.is_empty() {
+                    events.push((
+                        std::mem::take(&mut self.event),
+                        std::mem::take(&mut self.data),
+                    ));
+                }
+                self.event.clear();
+            } else if let Some(value) = line.strip_prefix("event:") {
+                self.event = value.trim_start().to_owned();
+            } else if let Some(value) = line.strip_prefix("data:") {
+                if !self.data.is_empty() {
+                    self.data.push('\n');
+                }
+                self.data.push_str(value.trim_start());
+            }
+            // Ignore comments, `id:` and `retry:` fields
+        }
+        events
+    }
+}
+
+/// Subscribe to the beacon events of `endpoint` and forward them to `tx` until the stream ends,
+/// or until it sends nothing for `idle_timeout`, i.e. the connection is half-open
+pub async fn subscribe_events(
+    endpoint: &BeaconEndpoint,
+    tx: &Sender<BeaconEvent>,
+    idle_timeout: Duration,
+) -> Result<()> {
+    let mut res = reqwest::Client::new()
+        .get(format!(
+            "{}/eth/v1/events?topics={EVENT_TOPICS}",
+            endpoint.url
+        ))
+        .header(reqwest::header::ACCEPT, "text/event-stream")
+        .headers(endpoint.headers.clone())
+        .send()
+        .await?;
+    if !res.status().is_success() {
+        return Err(anyhow!(
+            "eventstream returned not success code {}",
+            res.status().as_str()
+        ));
+    }
+    info!("subscribed to events of {}", endpoint.label());
+
+    let mut parser = SseParser::default();
+    loop {
+        let chunk = match time::timeout(idle_timeout, res.chunk()).await {
+            Err(_) => {
+                return Err(anyhow!(
+                    "no events from {} for {:?}",
+                    endpoint.label(),
+                    idle_timeout
+                ))
+            }
+            Ok(chunk) => match chunk? {
+                Some(chunk) => chunk,
+                None => break,
+            },
+        };
+        for (event, data) in parser.push(&chunk) {
+            match parse_event(&event, &data) {
+                Err(e) => debug!("error parsing {} event {}: {:?}", event, data, e),
+                Ok(None) => {}
+                Ok(Some(event)) => tx.send(event).await?,
+            }
+        }
+    }
+    Err(anyhow!("event stream of {} ended", endpoint.label()))
+}
+
+/// Subscribe to the events of the first endpoint in priority order, re-subscribing to the next
+/// one `retry_delay` after errors. While subscribed to a fallback endpoint, check every `recheck_interval` if a
+/// higher priority endpoint is ready again and switch back to it.
+pub async fn subscribe_events_with_failover(
+    endpoints: &[BeaconEndpoint],
+    tx: &Sender<BeaconEvent>,
+    idle_timeout: Duration,
+    retry_delay: Duration,
+    recheck_interval: Duration,
+) {
+    let mut next = 0;
+    loop {
+        let endpoint = &endpoints[next];
+        tokio::select! {
+            result = subscribe_events(endpoint, tx, idle_timeout) => {
+                if let Err(e) = result {
+                    error!("error on events of {}: {:?}", endpoint.label(), e);
+                }
+                next = (next + 1) % endpoints.len();
+                time::sleep(retry_delay).await;
+            }
+            ready = wait_for_ready_endpoint(&endpoints[..next], recheck_interval) => {
+                info!(
+                    "{} is ready again, switching events from {}",
+                    endpoints[ready].label(),
+                    endpoint.label()
+                );
+                next = ready;
+            }
+        }
+    }
+}
+
+/// Resolves with the position of the first endpoint of `endpoints` that is synced and not
+/// optimistic, checked every `interval`. Never resolves if `endpoints` is empty.
+async fn wait_for_ready_endpoint(endpoints: &[BeaconEndpoint], interval: Duration) -> usize {
+    if endpoints.is_empty() {
+        return std::future::pending().await;
+    }
+    loop {
+        time::sleep(interval).await;
+        for (i, endpoint) in endpoints.iter().enumerate() {
+            if fetch_syncing(endpoint)
+                .await
+                .is_ok_and(|syncing| syncing.is_ready())
+            {
+                return i;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sse_parser_test() {
+        let mut parser = SseParser::default();
+        assert_eq!(
+            parser.push(b": keep-alive\n\nevent: head\ndata: {\"sl"),
+            vec![]
+        );
+        assert_eq!(
+            parser.push(b"ot\":\"10\"}\n\nevent: chain_reorg\r\ndata: {}\r\n\r\n"),
+            vec![
+                ("head".to_owned(), "{\"slot\":\"10\"}".to_owned()),
+                ("chain_reorg".to_owned(), "{}".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reorg_crosses_epoch_test() {
+        assert!(!reorg_crosses_epoch(33, 1, 32));
+        assert!(reorg_crosses_epoch(33, 2, 32));
+        assert!(reorg_crosses_epoch(32, 1, 32));
+        assert!(!reorg_crosses_epoch(63, 31, 32));
+    }
+
+    #[test]
+    fn parse_event_test() {
+        assert_eq!(
+            parse_event(
+                "head",
+                r#"{"slot":"10","block":"0x00","epoch_transition":false}"#
+            )
+            .unwrap(),
+            Some(BeaconEvent::Head { slot: 10 })
+        );
+        assert_eq!(
+            parse_event(
+                "finalized_checkpoint",
+                r#"{"block":"0x00","state":"0x00","epoch":"2","execution_optimistic":false}"#
+            )
+            .unwrap(),
+            Some(BeaconEvent::FinalizedCheckpoint { epoch: 2 })
+        );
+        assert_eq!(
+            parse_event("chain_reorg", r#"{"slot":"200","depth":"50","epoch":"6"}"#).unwrap(),
+            Some(BeaconEvent::ChainReorg {
+                slot: 200,
+                depth: 50
+            })
+        );
+        assert_eq!(parse_event("block", "{}").unwrap(), None);
+        assert!(parse_event("head", "{}").is_err());
+    }
+
+    #[tokio::test]
+    async fn subscribe_events_idle_timeout() {
+        use hyper::service::{make_service_fn, service_fn};
+        use hyper::{Body, Response, Server};
+        use std::convert::Infallible;
+
+        // Sends the response headers and then nothing, as a half-open connection
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service_fn(|_| async {
+            Ok::<_, Infallible>(service_fn(|_| async {
+                let (sender, body) = Body::channel();
+                tokio::spawn(async move {
+                    time::sleep(Duration::from_secs(60)).await;
+                    drop(sender);
+                });
+                Ok::<_, Infallible>(Response::new(body))
+            }))
+        }));
+        let endpoint = BeaconEndpoint {
+            url: format!("http://{}", server.local_addr()),
+            headers: Default::default(),
+        };
+        tokio::spawn(server);
+
+        let (tx, _rx) = tokio::sync::mpsc::channel(1);
+        let result = time::timeout(
+            Duration::from_secs(5),
+            subscribe_events(&endpoint, &tx, Duration::from_millis(200)),
+        )
+        .await
+        .expect("idle subscription not timed out");
+        assert!(result.unwrap_err().to_string().contains("no events"));
+    }
+}
@@ -1,44 +1,191 @@
 use crate::config::fetch_genesis;
-use crate::ranges::parse_ranges;
+use crate::ranges::{
+    group_by_label, lint_ranges, parse_group_labels, parse_ranges, unknown_label_keys, LABEL_KEYS,
+};
 use crate::util::{current_epoch_start_slot, resolve_path_or_url, to_next_epoch_start};
 use anyhow::{anyhow, Context, Result};
-use clap::Parser;
-use config::{fetch_config, ConfigSpec, Genesis};
-use hyper::header::{HeaderName, CONTENT_TYPE};
+use backfill::{BackfillWriter, EpochRecord, OutputFormat};
+use bytes::Bytes;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use compare::{compare_states, StateDiff};
+use config::{
+    builtin_config, builtin_network, check_builtin_preset, fetch_config, load_config_yaml,
+    ConfigSpec, ForkName, Genesis,
+};
+use endpoints::{fetch_syncing, parse_endpoints, BeaconEndpoint};
+use events::{reorg_crosses_epoch, subscribe_events_with_failover, BeaconEvent};
+use hyper::header::CONTENT_TYPE;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, HeaderMap, Request, Response, Server};
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use metrics::{
-    set_gauge, HEAD_PARTICIPATION, INACTIVITY_SCORES, INDEXES_PER_GROUP, SOURCE_PARTICIPATION,
-    TARGET_PARTICIPATION,
+    inc_counter_by, set_gauge, set_or_remove_gauge, BALANCE_AVG, BALANCE_MAX, BALANCE_MIN,
+    BALANCE_SUM, CHAIN_REORGS, CL_PARTICIPATION, COMPARE_BALANCES_DIFF, COMPARE_FETCH_ERRORS,
+    COMPARE_INACTIVITY_SCORES_DIFF, COMPARE_PARTICIPATION_DIFF, COMPARE_STATE_SLOT,
+    CONSOLIDATING_VALIDATORS, CURRENT_HEAD_PARTICIPATION, CURRENT_JUSTIFIED_EPOCH,
+    CURRENT_PARTICIPATION_SLOT, CURRENT_SOURCE_PARTICIPATION, CURRENT_TARGET_PARTICIPATION,
+    DECODE_ERRORS, EL_PARTICIPATION, ENDPOINT_FETCHES, ENTITY_PARTICIPATION, EPOCHS_SINCE_FINALITY,
+    EPOCH_CROSSING_CHAIN_REORGS, EXCLUDED_INDEXES_PER_GROUP, FETCH_ENDPOINT, FINALIZED_EPOCH,
+    HEAD_PARTICIPATION, HEAD_PARTICIPATION_BALANCE, INACTIVITY_SCORES,
+    INDEXES_NOT_IN_REGISTRY_PER_GROUP, INDEXES_PER_GROUP, JUSTIFICATION_BITS,
+    LAST_CHAIN_REORG_DEPTH, PARTICIPATION_EPOCH, PENDING_CONSOLIDATIONS, PENDING_DEPOSITS,
+    PENDING_DEPOSITS_AMOUNT, PENDING_DEPOSITS_UNATTRIBUTED, PENDING_DEPOSITS_UNATTRIBUTED_AMOUNT,
+    PENDING_PARTIAL_WITHDRAWALS, PENDING_PARTIAL_WITHDRAWALS_AMOUNT, PREVIOUS_JUSTIFIED_EPOCH,
+    PROPOSALS_MISSED, PROPOSALS_PROPOSED, PROPOSALS_SCHEDULED, RANGE_LABELS, SOURCE_PARTICIPATION,
+    SOURCE_PARTICIPATION_BALANCE, STATE_SLOT, SYNC_COMMITTEE_DUTIES, SYNC_COMMITTEE_MEMBERS,
+    SYNC_COMMITTEE_PARTICIPATION, TARGET_PARTICIPATION, TARGET_PARTICIPATION_BALANCE,
+    UNRESOLVED_PUBKEYS, VALIDATORS_BY_STATUS,
 };
+use pending_queues::{group_pending_queues, PendingQueues};
 use prettytable::{format, Cell, Row, Table};
 use prometheus::{Encoder, TextEncoder};
-use ssz_state::{deserialize_partial_state, StatePartial};
+use proposals::{
+    fetch_proposals, fetch_proposer_duties, group_proposals, EpochProposerDuties, ProposalsByRange,
+};
+use pubkeys::{load_pubkey_groups, Groups, PubkeyGroups};
+use reload::{reload_groups_on_change, set_reload_to_metrics};
+use serde_json::json;
+use ssz_state::{
+    deserialize_partial_state, StatePartial, Validator, ValidatorStatus, TIMELY_HEAD,
+    TIMELY_SOURCE, TIMELY_TARGET,
+};
+use std::collections::{BTreeSet, HashMap};
 use std::convert::Infallible;
+use std::fs::{self, File};
+use std::future::Future;
+use std::io::{self, BufWriter, Write};
 use std::net::SocketAddr;
-use std::str::FromStr;
+use std::panic;
+use std::sync::Arc;
 use std::time::Duration;
+use sync_committee::{
+    fetch_epoch_sync_aggregates, group_sync_committee_participation, SyncCommitteeByRange,
+};
+use tokio::sync::{mpsc, watch};
 use tokio::time;
+use withdrawals::{parse_withdrawal_names, WithdrawalGrouping};
 
 //use ssz_state::parse_epoch_participation;
 //use ssz_state::ConfigSpec;
 
+mod backfill;
+mod compare;
 mod config;
+mod endpoints;
+mod events;
 mod metrics;
+mod pending_queues;
+mod phase0;
+mod proposals;
+mod pubkeys;
 mod ranges;
+mod reload;
 mod ssz_state;
+mod sync_committee;
 mod util;
+mod withdrawals;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 struct Cli {
-    /// Beacon HTTP API URL: http://1.2.3.4:4000
-    url: String,
-    /// Extra headers sent to each request to the beacon node API at `url`.
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    beacon: BeaconArgs,
+    #[command(flatten)]
+    network: NetworkArgs,
+    #[command(flatten)]
+    ranges: RangesArgs,
+    /// Dump participation ranges print to stderr on each fetch
+    #[arg(long)]
+    dump: bool,
+    /// Fetch the blocks of the previous epoch on each fetch to compute sync committee
+    /// participation by range
+    #[arg(long)]
+    sync_committee: bool,
+    /// Fetch proposer duties and block headers on each epoch to count proposed and missed
+    /// blocks by range
+    #[arg(long)]
+    proposals: bool,
+    /// Fetch the state at the same slot from all beacon URLs on each fetch, and export the count
+    /// of validators whose participation, inactivity score or balance differ per node. Syncing or
+    /// optimistic nodes are not compared
+    #[arg(long)]
+    compare: bool,
+    /// Fetch the state on the first head event of each epoch and on reorgs crossing an epoch
+    /// boundary, subscribing to the beacon node events instead of following the local clock
+    #[arg(long)]
+    events: bool,
+    /// Fetch the head state every this many slots to export the current epoch participation
+    /// gauges mid-epoch. Without it they are not exported
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    current_epoch_poll_slots: Option<u64>,
+    /// Interval in seconds to download again the groups files given as URLs. Local files are
+    /// reloaded on change
+    #[arg(long, default_value_t = 300)]
+    ranges_reload_interval: u64,
+    /// Metrics server port
+    #[arg(long, short, default_value_t = 8080)]
+    port: u16,
+    /// Metrics server bind address
+    #[arg(long, default_value = "127.0.0.1")]
+    address: String,
+    /// Increase verbosity level
+    #[arg(long, short, global = true)]
+    verbose: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Fetch the states of past epochs by slot from an archive node and write the participation
+    /// of each epoch by range, instead of serving metrics
+    Backfill(BackfillArgs),
+    /// Decode an SSZ state file and print the participation by range, without a beacon node
+    Analyze(AnalyzeArgs),
+    /// Check a ranges file for unparseable lines, empty or inverted ranges, overlaps between
+    /// groups and gaps, and its coverage of the validator registry. Exits non-zero on errors
+    ValidateRanges(ValidateRangesArgs),
+}
+
+#[derive(Args)]
+struct BeaconArgs {
+    /// Beacon HTTP API URLs in priority order: http://1.2.3.4:4000. Nodes that are down,
+    /// syncing or optimistic are skipped in favor of the next URL
+    #[arg(required = true)]
+    urls: Vec<String>,
+    /// Extra headers sent to each request to all beacon node APIs.
     /// Same format as curl: `-H "Authorization: Bearer {token}"`
     #[arg(long, short = 'H')]
     headers: Option<Vec<String>>,
+    /// Extra headers sent only to the beacon node API at one of `urls`, as `{url}={header}`:
+    /// `--url-header "http://1.2.3.4:4000=Authorization: Bearer {token}"`
+    #[arg(long)]
+    url_header: Option<Vec<String>>,
+}
+
+#[derive(Args)]
+struct NetworkArgs {
+    /// Use the built-in config and genesis of a known network instead of fetching them from the
+    /// beacon node: mainnet, gnosis, holesky, sepolia, hoodi, chiado or minimal
+    #[arg(long, conflicts_with = "config_file")]
+    network: Option<String>,
+    /// Local path or URL of a consensus-specs style config.yaml to use instead of fetching the
+    /// config from the beacon node. Requires --preset-file
+    #[arg(long, requires = "preset_file")]
+    config_file: Option<String>,
+    /// Local path or URL of a consensus-specs style preset YAML of the --config-file network.
+    /// Repeat for presets split in one file per fork
+    #[arg(long)]
+    preset_file: Option<Vec<String>>,
+    /// Genesis unix time in seconds. Defaults to the --network genesis, else fetched from the
+    /// beacon node
+    #[arg(long)]
+    genesis_time: Option<u64>,
+}
+
+#[derive(Args, Clone)]
+struct RangesArgs {
     /// Index ranges to group IDs as JSON or TXT. Example:
     /// `{"0..100": "lh-geth-0", "100..200": "lh-geth-1"}
     #[arg(long)]
@@ -47,26 +194,121 @@ struct Cli {
     /// with the format as defined in --ranges
     #[arg(long)]
     ranges_file: Option<String>,
-    /// Dump participation ranges print to stderr on each fetch
+    /// Local path or URL of a file grouping validators by pubkey instead of index, as YAML or
+    /// JSON mapping each group name to a list of pubkeys or to a path or URL of a file with one
+    /// pubkey per line. Pubkeys are resolved to indexes on each fetch
     #[arg(long)]
-    dump: bool,
-    /// Metrics server port
-    #[arg(long, short, default_value_t = 8080)]
-    port: u16,
-    /// Metrics server bind address
-    #[arg(long, default_value = "127.0.0.1")]
-    address: String,
-    /// Increase verbosity level
+    pubkeys_file: Option<String>,
+    /// Local path or URL of a file mapping withdrawal addresses of 0x01 and 0x02 credentials to
+    /// group names, as YAML or JSON `{"0x388c...": "entityA"}` or TXT with `0x388c... entityA`
+    /// lines. Validators are grouped by their credentials on each fetch
+    #[arg(long)]
+    withdrawal_addresses_file: Option<String>,
+    /// Group the validators of each withdrawal address with at least this many validators,
+    /// named by the address. Addresses in --withdrawal-addresses-file keep their name
+    #[arg(long)]
+    withdrawal_threshold: Option<usize>,
+    /// Fail on unparseable lines, empty or inverted ranges and overlaps between groups in the
+    /// ranges, instead of skipping or merging them
+    #[arg(long)]
+    strict: bool,
+}
+
+#[derive(Args)]
+struct AnalyzeArgs {
+    /// Path to an SSZ encoded BeaconState
+    #[arg(long)]
+    state: String,
+    /// Config of the state network: mainnet, gnosis, holesky, sepolia, hoodi, chiado or minimal
+    #[arg(long, default_value = "mainnet")]
+    config: String,
+    /// Fork of the state, required if its fork version is not of the config network
+    #[arg(long)]
+    fork: Option<ForkName>,
+    #[command(flatten)]
+    ranges: RangesArgs,
+    /// Output format
+    #[arg(long, value_enum, default_value_t = AnalyzeFormat::Table)]
+    format: AnalyzeFormat,
+}
+
+#[derive(Args)]
+struct ValidateRangesArgs {
+    /// Local path or URL of a ranges file, as JSON, YAML or TXT
+    ranges_file: String,
+    /// Validator count of the registry to check the coverage against
+    #[arg(long, conflicts_with = "beacon_url")]
+    validator_count: Option<usize>,
+    /// Beacon HTTP API URLs to fetch the head state validator count from, in priority order
+    #[arg(long)]
+    beacon_url: Option<Vec<String>>,
+    /// Extra headers sent to each request to all beacon node APIs
+    #[arg(long, short = 'H', requires = "beacon_url")]
+    headers: Option<Vec<String>>,
+    #[command(flatten)]
+    network: NetworkArgs,
+    /// Also fail on gaps and incomplete coverage of the registry
+    #[arg(long)]
+    strict: bool,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum AnalyzeFormat {
+    /// Same table as --dump
+    Table,
+    Json,
+}
+
+#[derive(Args)]
+struct BackfillArgs {
+    #[command(flatten)]
+    beacon: BeaconArgs,
+    #[command(flatten)]
+    network: NetworkArgs,
+    #[command(flatten)]
+    ranges: RangesArgs,
+    /// First epoch to backfill
+    #[arg(long)]
+    from_epoch: u64,
+    /// Last epoch to backfill, inclusive
+    #[arg(long)]
+    to_epoch: u64,
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+    format: OutputFormat,
+    /// Output file path, defaults to stdout
     #[arg(long, short)]
-    verbose: bool,
+    output: Option<String>,
 }
 
 type IndexGroups = Vec<(String, Vec<usize>)>;
+/// Ratios and averages are None if the group has no validators to average, i.e. none active or
+/// none in the registry yet
 struct RangeSummary {
-    target_participation_ratio: f32,
-    head_participation_ratio: f32,
-    source_participation_ratio: f32,
-    inactivity_scores_avg: f32,
+    target_participation_ratio: Option<f32>,
+    head_participation_ratio: Option<f32>,
+    source_participation_ratio: Option<f32>,
+    /// Participation ratios of the in-progress current epoch, as of the state slot
+    current_target_participation_ratio: Option<f32>,
+    current_head_participation_ratio: Option<f32>,
+    current_source_participation_ratio: Option<f32>,
+    /// Participation ratios weighted by effective balance, as the spec computes them
+    target_participation_balance_ratio: Option<f32>,
+    head_participation_balance_ratio: Option<f32>,
+    source_participation_balance_ratio: Option<f32>,
+    inactivity_scores_avg: Option<f32>,
+    /// Balances in Gwei
+    balance_sum: u64,
+    balance_avg: Option<f32>,
+    balance_min: u64,
+    balance_max: u64,
+    /// Count of indexes in the registry not active in the previous epoch, excluded from
+    /// participation ratios
+    excluded_indexes: usize,
+    /// Count of indexes beyond the current validator count, i.e. not deposited yet
+    indexes_not_in_registry: usize,
+    /// Count of indexes per status, in the order of `ValidatorStatus::ALL`
+    status_counts: [usize; ValidatorStatus::ALL.len()],
 }
 type ParticipationByRange = Vec<(String, Vec<usize>, RangeSummary)>;
 
@@ -84,21 +326,32 @@ async fn handle_metrics_server_request(_req: Request<Body>) -> Result<Response<B
 }
 
 const CONTENT_TYPE_SSZ: &str = "application/octet-stream";
+const HEADER_CONSENSUS_VERSION: &str = "Eth-Consensus-Version";
 
+/// Fetch and decode the state at `state_id`, i.e. `head` or a slot. Returns None if the node
+/// does not have that state
 async fn fetch_epoch_participation(
     config: &ConfigSpec,
-    beacon_url: &str,
-    extra_headers: &HeaderMap,
-) -> Result<StatePartial> {
-    debug!("fetching head state from {}", beacon_url);
+    genesis: &Genesis,
+    endpoint: &BeaconEndpoint,
+    state_id: &str,
+) -> Result<Option<StatePartial>> {
+    let beacon_url = &endpoint.url;
+    debug!("fetching {} state from {}", state_id, endpoint.label());
 
     let req = reqwest::Client::new()
-        .get(format!("{beacon_url}/eth/v2/debug/beacon/states/head",))
+        .get(format!(
+            "{beacon_url}/eth/v2/debug/beacon/states/{state_id}"
+        ))
         .header(reqwest::header::ACCEPT, CONTENT_TYPE_SSZ)
-        .headers(extra_headers.clone())
+        .headers(endpoint.headers.clone())
         .send()
         .await?;
 
+    // Some nodes do not serve states at missed slots
+    if req.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
     // Guard against bad responses, else this function will attempt to decode a 404 html as if it
     // was an SSZ state
     if !req.status().is_success() {
@@ -108,7 +361,7 @@ async fn fetch_epoch_participation(
         ));
     }
 
-    debug!("fetch head state request status {}", req.status());
+    debug!("fetch state request status {}", req.status());
 
     // Additional guard in case the server sends JSON instead of SSZ. Could happen if a proxy or
     // some middleware strips the CONTENT_TYPE header out of this request
@@ -124,65 +377,407 @@ async fn fetch_epoch_participation(
         }
     }
 
+    // Fork of the returned state, to pick the state layout
+    let fork_hint = req
+        .headers()
+        .get(HEADER_CONSENSUS_VERSION)
+        .and_then(|fork| fork.to_str().ok())
+        .and_then(|fork| fork.parse().ok());
+
     let state_buf = req.bytes().await?;
 
-    debug!("fetch head state downloaded body size {}", state_buf.len());
+    debug!("fetch state downloaded body size {}", state_buf.len());
+
+    let state = match deserialize_partial_state(config, &state_buf, fork_hint)
+        .and_then(|state| check_state_genesis(&state, genesis).map(|_| state))
+    {
+        Ok(state) => state,
+        Err(e) => {
+            inc_counter_by(&DECODE_ERRORS, &[&endpoint.label()], 1);
+            return Err(e.context(format!("decoding state of {}", endpoint.label())));
+        }
+    };
+    debug!(
+        "decoded {} state at slot {}",
+        state.fork.as_str(),
+        state.slot
+    );
+    Ok(Some(state))
+}
+
+/// A state of another network decodes fine if it has the same preset, reject it
+fn check_state_genesis(state: &StatePartial, genesis: &Genesis) -> Result<()> {
+    if state.genesis_time != genesis.genesis_time {
+        return Err(anyhow!(
+            "state genesis_time {} != chain genesis_time {}, node is on another network",
+            state.genesis_time,
+            genesis.genesis_time
+        ));
+    }
+    Ok(())
+}
+
+/// Fetch the state at `epoch_start_slot`, whose previous epoch participation describes the whole
+/// previous epoch. If the node does not serve it, fall back to the last slots of the previous
+/// epoch, skipping missed slots, rotated to the boundary with `rotate_to_epoch_boundary`.
+async fn fetch_state_at_epoch_boundary(
+    config: &ConfigSpec,
+    genesis: &Genesis,
+    endpoint: &BeaconEndpoint,
+    epoch_start_slot: u64,
+) -> Result<StatePartial> {
+    let first_slot = epoch_start_slot.saturating_sub(config.slots_per_epoch);
+    for slot in (first_slot..=epoch_start_slot).rev() {
+        match fetch_epoch_participation(config, genesis, endpoint, &slot.to_string()).await? {
+            Some(mut state) => {
+                rotate_to_epoch_boundary(&mut state, config, epoch_start_slot);
+                return Ok(state);
+            }
+            None => debug!("no state at slot {} in {}", slot, endpoint.label()),
+        }
+    }
+    Err(anyhow!(
+        "no state found from slot {} to {}",
+        epoch_start_slot,
+        first_slot
+    ))
+}
+
+/// A state of the epoch before `epoch_start_slot` has the participation of that epoch as its
+/// current epoch participation. Rotate it as the epoch transition does, so its participation
+/// describes the same epochs as the state at the boundary slot. Only the participation is
+/// rotated, `slot` stays the slot the other fields like balances and inactivity scores are of.
+fn rotate_to_epoch_boundary(state: &mut StatePartial, config: &ConfigSpec, epoch_start_slot: u64) {
+    let boundary_epoch = epoch_start_slot / config.slots_per_epoch;
+    if state.participation_epoch + 1 >= boundary_epoch {
+        return;
+    }
+    debug!(
+        "rotating participation of state at slot {} to epoch boundary slot {}",
+        state.slot, epoch_start_slot
+    );
+    state.previous_epoch_participation = std::mem::take(&mut state.current_epoch_participation);
+    state.current_epoch_participation = vec![0; state.validators.len()];
+    state.participation_epoch = boundary_epoch - 1;
+}
+
+/// Fetch the epoch boundary state from the first ready endpoint in priority order, falling back to
+/// the next endpoint on errors. Returns the endpoint that served the state.
+async fn fetch_state_from_endpoints<'a>(
+    config: &ConfigSpec,
+    genesis: &Genesis,
+    endpoints: &'a [BeaconEndpoint],
+    epoch_start_slot: u64,
+) -> Result<(&'a BeaconEndpoint, StatePartial)> {
+    for endpoint in endpoints {
+        let label = endpoint.label();
+        match fetch_syncing(endpoint).await {
+            Err(e) => {
+                warn!("skipping {}, error fetching syncing status: {:?}", label, e);
+                continue;
+            }
+            Ok(syncing) if !syncing.is_ready() => {
+                warn!("skipping {}, node is syncing or optimistic", label);
+                continue;
+            }
+            Ok(_) => {}
+        }
+
+        match fetch_state_at_epoch_boundary(config, genesis, endpoint, epoch_start_slot).await {
+            Err(e) => warn!("error fetching state from {}: {:?}", label, e),
+            Ok(state) => {
+                set_fetch_endpoint_to_metrics(endpoints, endpoint);
+                return Ok((endpoint, state));
+            }
+        }
+    }
+    Err(anyhow!(
+        "no beacon endpoint available out of {}",
+        endpoints.len()
+    ))
+}
+
+fn set_fetch_endpoint_to_metrics(endpoints: &[BeaconEndpoint], serving: &BeaconEndpoint) {
+    for endpoint in endpoints {
+        let is_serving = endpoint.url == serving.url;
+        set_gauge(
+            &FETCH_ENDPOINT,
+            &[&endpoint.label()],
+            if is_serving { 1.0 } else { 0.0 },
+        );
+    }
+    inc_counter_by(&ENDPOINT_FETCHES, &[&serving.label()], 1);
+}
+
+/// Fetch the epoch boundary state of `reference` from all other ready endpoints and export their
+/// disagreements with it. Syncing or optimistic nodes are skipped, their state is expected to
+/// differ
+async fn compare_endpoints(
+    config: &ConfigSpec,
+    genesis: &Genesis,
+    endpoints: &[BeaconEndpoint],
+    reference_endpoint: &BeaconEndpoint,
+    reference: &StatePartial,
+) {
+    set_state_diff_to_metrics(
+        &reference_endpoint.label(),
+        &compare_states(reference, reference),
+    );
+
+    for endpoint in endpoints
+        .iter()
+        .filter(|endpoint| endpoint.url != reference_endpoint.url)
+    {
+        let label = endpoint.label();
+        match fetch_syncing(endpoint).await {
+            Err(e) => {
+                error!(
+                    "error fetching syncing status of {} to compare: {:?}",
+                    label, e
+                );
+                inc_counter_by(&COMPARE_FETCH_ERRORS, &[&label], 1);
+                continue;
+            }
+            Ok(syncing) if !syncing.is_ready() => {
+                warn!("not comparing {}, node is syncing or optimistic", label);
+                remove_state_diff_from_metrics(&label);
+                continue;
+            }
+            Ok(_) => {}
+        }
+        // Same fallback as the reference, in case its boundary slot was missed
+        let epoch_start_slot = (reference.participation_epoch + 1) * config.slots_per_epoch;
+        match fetch_state_at_epoch_boundary(config, genesis, endpoint, epoch_start_slot).await {
+            Err(e) => {
+                error!("error fetching state from {} to compare: {:?}", label, e);
+                inc_counter_by(&COMPARE_FETCH_ERRORS, &[&label], 1);
+            }
+            Ok(state) => {
+                let diff = compare_states(reference, &state);
+                if !diff.is_empty() {
+                    warn!(
+                        "state of {} at slot {} disagrees with {}: {:?}",
+                        label,
+                        reference.slot,
+                        reference_endpoint.label(),
+                        diff
+                    );
+                }
+                set_state_diff_to_metrics(&label, &diff);
+            }
+        }
+    }
+}
 
-    deserialize_partial_state(config, &state_buf)
+/// Remove the series of a node not compared, to not export its last disagreements as current
+fn remove_state_diff_from_metrics(node: &str) {
+    let _ = COMPARE_STATE_SLOT.remove_label_values(&[node]);
+    for epoch in ["previous", "current"] {
+        let _ = COMPARE_PARTICIPATION_DIFF.remove_label_values(&[node, epoch]);
+    }
+    let _ = COMPARE_INACTIVITY_SCORES_DIFF.remove_label_values(&[node]);
+    let _ = COMPARE_BALANCES_DIFF.remove_label_values(&[node]);
 }
 
-// https://github.com/ethereum/consensus-specs/blob/4a27f855439c16612ab1ae3995d71bed54f979ea/specs/altair/beacon-chain.md#participation-flag-indices
-const TIMELY_SOURCE_FLAG_INDEX: u8 = 0;
-const TIMELY_TARGET_FLAG_INDEX: u8 = 1;
-const TIMELY_HEAD_FLAG_INDEX: u8 = 2;
-const TIMELY_SOURCE: u8 = 1 << TIMELY_SOURCE_FLAG_INDEX;
-const TIMELY_TARGET: u8 = 1 << TIMELY_TARGET_FLAG_INDEX;
-const TIMELY_HEAD: u8 = 1 << TIMELY_HEAD_FLAG_INDEX;
+fn set_state_diff_to_metrics(node: &str, diff: &StateDiff) {
+    set_gauge(&COMPARE_STATE_SLOT, &[node], diff.slot as f64);
+    set_gauge(
+        &COMPARE_PARTICIPATION_DIFF,
+        &[node, "previous"],
+        diff.previous_epoch_participation as f64,
+    );
+    set_gauge(
+        &COMPARE_PARTICIPATION_DIFF,
+        &[node, "current"],
+        diff.current_epoch_participation as f64,
+    );
+    set_gauge(
+        &COMPARE_INACTIVITY_SCORES_DIFF,
+        &[node],
+        diff.inactivity_scores as f64,
+    );
+    set_gauge(&COMPARE_BALANCES_DIFF, &[node], diff.balances as f64);
+}
 
 fn has_flag(flag: u8, mask: u8) -> bool {
     flag & mask == mask
 }
 
-fn participation_avg(participation: &[u8], indexes: &[usize], flag_mask: u8) -> f32 {
+fn participation_avg(participation: &[u8], indexes: &[usize], flag_mask: u8) -> Option<f32> {
+    if indexes.is_empty() {
+        return None;
+    }
     let participant_sum: u32 = indexes
         .iter()
         .map(|index| has_flag(participation[*index], flag_mask) as u32)
         .sum::<u32>();
-    participant_sum as f32 / indexes.len() as f32
+    Some(participant_sum as f32 / indexes.len() as f32)
 }
 
-fn score_avg(values: &[u64], indexes: &[usize]) -> f32 {
+fn participation_balance_avg(
+    participation: &[u8],
+    validators: &[Validator],
+    indexes: &[usize],
+    flag_mask: u8,
+) -> Option<f32> {
+    let mut participant_balance: u64 = 0;
+    let mut total_balance: u64 = 0;
+    for index in indexes {
+        let effective_balance = validators[*index].effective_balance;
+        total_balance += effective_balance;
+        if has_flag(participation[*index], flag_mask) {
+            participant_balance += effective_balance;
+        }
+    }
+    if total_balance == 0 {
+        return None;
+    }
+    Some(participant_balance as f32 / total_balance as f32)
+}
+
+fn score_avg(values: &[u64], indexes: &[usize]) -> Option<f32> {
+    if indexes.is_empty() {
+        return None;
+    }
     let sum: u64 = indexes.iter().map(|index| values[*index]).sum();
-    sum as f32 / indexes.len() as f32
+    Some(sum as f32 / indexes.len() as f32)
+}
+
+fn balance_sum(balances: &[u64], indexes: &[usize]) -> u64 {
+    indexes.iter().map(|index| balances[*index]).sum()
+}
+
+fn balance_min(balances: &[u64], indexes: &[usize]) -> u64 {
+    indexes
+        .iter()
+        .map(|index| balances[*index])
+        .min()
+        .unwrap_or(0)
+}
+
+fn balance_max(balances: &[u64], indexes: &[usize]) -> u64 {
+    indexes
+        .iter()
+        .map(|index| balances[*index])
+        .max()
+        .unwrap_or(0)
+}
+
+/// Returns the subset of `indexes` active at `epoch`. Indexes not yet in the registry are not
+/// active.
+fn active_indexes(validators: &[Validator], indexes: &[usize], epoch: u64) -> Vec<usize> {
+    indexes
+        .iter()
+        .filter(|index| {
+            validators
+                .get(**index)
+                .is_some_and(|validator| validator.is_active(epoch))
+        })
+        .copied()
+        .collect()
+}
+
+fn status_counts(
+    validators: &[Validator],
+    indexes: &[usize],
+    epoch: u64,
+) -> [usize; ValidatorStatus::ALL.len()] {
+    let mut counts = [0; ValidatorStatus::ALL.len()];
+    for index in indexes {
+        let status = validators[*index].status(epoch);
+        counts[status as usize] += 1;
+    }
+    counts
 }
 
 fn group_target_participation(
     index_groups: &IndexGroups,
     state: &StatePartial,
 ) -> ParticipationByRange {
+    let previous_epoch = state.participation_epoch;
+    let epoch = previous_epoch + 1;
+    let validator_count = state.validators.len();
     index_groups
         .iter()
-        .map(|(range_name, indexes)| {
+        .map(|(range_name, all_indexes)| {
+            // Ranges may be prepared ahead of deposits, clip indexes not yet in the registry
+            let indexes: Vec<usize> = all_indexes
+                .iter()
+                .filter(|index| **index < validator_count)
+                .copied()
+                .collect();
+            if indexes.len() < all_indexes.len() {
+                debug!(
+                    "range {} has {} indexes not yet in registry of {} validators",
+                    range_name,
+                    all_indexes.len() - indexes.len(),
+                    validator_count
+                );
+            }
+            let indexes = &indexes;
+            // Only validators active in the previous epoch are expected to participate
+            let eligible_indexes = active_indexes(&state.validators, indexes, previous_epoch);
+            let current_eligible_indexes = active_indexes(&state.validators, indexes, epoch);
             (
                 range_name.clone(),
-                indexes.clone(),
+                all_indexes.clone(),
                 RangeSummary {
                     target_participation_ratio: participation_avg(
                         &state.previous_epoch_participation,
-                        indexes,
+                        &eligible_indexes,
                         TIMELY_TARGET,
                     ),
                     source_participation_ratio: participation_avg(
                         &state.previous_epoch_participation,
-                        indexes,
+                        &eligible_indexes,
                         TIMELY_SOURCE,
                     ),
                     head_participation_ratio: participation_avg(
                         &state.previous_epoch_participation,
-                        indexes,
+                        &eligible_indexes,
+                        TIMELY_HEAD,
+                    ),
+                    current_target_participation_ratio: participation_avg(
+                        &state.current_epoch_participation,
+                        &current_eligible_indexes,
+                        TIMELY_TARGET,
+                    ),
+                    current_source_participation_ratio: participation_avg(
+                        &state.current_epoch_participation,
+                        &current_eligible_indexes,
+                        TIMELY_SOURCE,
+                    ),
+                    current_head_participation_ratio: participation_avg(
+                        &state.current_epoch_participation,
+                        &current_eligible_indexes,
+                        TIMELY_HEAD,
+                    ),
+                    target_participation_balance_ratio: participation_balance_avg(
+                        &state.previous_epoch_participation,
+                        &state.validators,
+                        &eligible_indexes,
+                        TIMELY_TARGET,
+                    ),
+                    source_participation_balance_ratio: participation_balance_avg(
+                        &state.previous_epoch_participation,
+                        &state.validators,
+                        &eligible_indexes,
+                        TIMELY_SOURCE,
+                    ),
+                    head_participation_balance_ratio: participation_balance_avg(
+                        &state.previous_epoch_participation,
+                        &state.validators,
+                        &eligible_indexes,
                         TIMELY_HEAD,
                     ),
                     inactivity_scores_avg: score_avg(&state.inactivity_scores, indexes),
+                    balance_sum: balance_sum(&state.balances, indexes),
+                    balance_avg: score_avg(&state.balances, indexes),
+                    balance_min: balance_min(&state.balances, indexes),
+                    balance_max: balance_max(&state.balances, indexes),
+                    excluded_indexes: indexes.len() - eligible_indexes.len(),
+                    indexes_not_in_registry: all_indexes.len() - indexes.len(),
+                    status_counts: status_counts(&state.validators, indexes, epoch),
                 },
             )
         })
@@ -191,30 +786,215 @@ fn group_target_participation(
 
 fn set_participation_to_metrics(participation_by_range: &ParticipationByRange) {
     for (range_name, indexes, summary) in participation_by_range.iter() {
-        set_gauge(
+        set_or_remove_gauge(
             &SOURCE_PARTICIPATION,
             &[range_name],
-            summary.source_participation_ratio as f64,
+            summary.source_participation_ratio.map(f64::from),
         );
-        set_gauge(
+        set_or_remove_gauge(
             &TARGET_PARTICIPATION,
             &[range_name],
-            summary.target_participation_ratio as f64,
+            summary.target_participation_ratio.map(f64::from),
         );
-        set_gauge(
+        set_or_remove_gauge(
             &HEAD_PARTICIPATION,
             &[range_name],
-            summary.head_participation_ratio as f64,
+            summary.head_participation_ratio.map(f64::from),
         );
-        set_gauge(
+        set_or_remove_gauge(
+            &SOURCE_PARTICIPATION_BALANCE,
+            &[range_name],
+            summary.source_participation_balance_ratio.map(f64::from),
+        );
+        set_or_remove_gauge(
+            &TARGET_PARTICIPATION_BALANCE,
+            &[range_name],
+            summary.target_participation_balance_ratio.map(f64::from),
+        );
+        set_or_remove_gauge(
+            &HEAD_PARTICIPATION_BALANCE,
+            &[range_name],
+            summary.head_participation_balance_ratio.map(f64::from),
+        );
+        set_or_remove_gauge(
             &INACTIVITY_SCORES,
             &[range_name],
-            summary.inactivity_scores_avg as f64,
+            summary.inactivity_scores_avg.map(f64::from),
         );
         set_gauge(&INDEXES_PER_GROUP, &[range_name], indexes.len() as f64);
+        set_gauge(
+            &EXCLUDED_INDEXES_PER_GROUP,
+            &[range_name],
+            summary.excluded_indexes as f64,
+        );
+        set_gauge(
+            &INDEXES_NOT_IN_REGISTRY_PER_GROUP,
+            &[range_name],
+            summary.indexes_not_in_registry as f64,
+        );
+        set_gauge(&BALANCE_SUM, &[range_name], summary.balance_sum as f64);
+        set_or_remove_gauge(
+            &BALANCE_AVG,
+            &[range_name],
+            summary.balance_avg.map(f64::from),
+        );
+        set_gauge(&BALANCE_MIN, &[range_name], summary.balance_min as f64);
+        set_gauge(&BALANCE_MAX, &[range_name], summary.balance_max as f64);
+        for (status, count) in ValidatorStatus::ALL.iter().zip(summary.status_counts) {
+            set_gauge(
+                &VALIDATORS_BY_STATUS,
+                &[range_name, status.as_str()],
+                count as f64,
+            );
+        }
+    }
+}
+
+/// Export the participation of the in-progress epoch as of `slot`. Only for mid-epoch states, the
+/// current epoch of an epoch boundary state has barely started
+fn set_current_participation_to_metrics(participation_by_range: &ParticipationByRange, slot: u64) {
+    CURRENT_PARTICIPATION_SLOT.set(slot as f64);
+    for (range_name, _, summary) in participation_by_range.iter() {
+        set_or_remove_gauge(
+            &CURRENT_SOURCE_PARTICIPATION,
+            &[range_name],
+            summary.current_source_participation_ratio.map(f64::from),
+        );
+        set_or_remove_gauge(
+            &CURRENT_TARGET_PARTICIPATION,
+            &[range_name],
+            summary.current_target_participation_ratio.map(f64::from),
+        );
+        set_or_remove_gauge(
+            &CURRENT_HEAD_PARTICIPATION,
+            &[range_name],
+            summary.current_head_participation_ratio.map(f64::from),
+        );
+    }
+}
+
+/// Export the labels of ranges with structured names, and their participation aggregated by
+/// entity and by client
+fn set_range_labels_to_metrics(index_groups: &IndexGroups, state: &StatePartial) -> Result<()> {
+    // Drop the series of ranges and label values not in the groups anymore
+    RANGE_LABELS.reset();
+    ENTITY_PARTICIPATION.reset();
+    CL_PARTICIPATION.reset();
+    EL_PARTICIPATION.reset();
+    for (range_name, _) in index_groups {
+        if let Some(labels) = parse_group_labels(range_name) {
+            let mut label_values = vec![range_name.as_str()];
+            label_values.extend(labels.iter().map(String::as_str));
+            set_gauge(&RANGE_LABELS, &label_values, 1.0);
+        }
+    }
+
+    for (gauge, key) in [
+        (&*ENTITY_PARTICIPATION, "entity"),
+        (&*CL_PARTICIPATION, "cl"),
+        (&*EL_PARTICIPATION, "el"),
+    ] {
+        let groups = group_by_label(index_groups, key)?;
+        for (value, _, summary) in group_target_participation(&groups, state) {
+            for (flag, ratio) in [
+                ("source", summary.source_participation_ratio),
+                ("target", summary.target_participation_ratio),
+                ("head", summary.head_participation_ratio),
+            ] {
+                set_or_remove_gauge(gauge, &[&value, flag], ratio.map(f64::from));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The previous epoch participation gauges describe `participation_epoch`, the other state
+/// gauges the state at `slot`
+fn set_participation_epoch_to_metrics(state: &StatePartial) {
+    PARTICIPATION_EPOCH.set(state.participation_epoch as f64);
+    STATE_SLOT.set(state.slot as f64);
+}
+
+fn set_finality_to_metrics(state: &StatePartial, config: &ConfigSpec) {
+    let epoch = state.slot / config.slots_per_epoch;
+    FINALIZED_EPOCH.set(state.finalized_checkpoint.epoch as f64);
+    CURRENT_JUSTIFIED_EPOCH.set(state.current_justified_checkpoint.epoch as f64);
+    PREVIOUS_JUSTIFIED_EPOCH.set(state.previous_justified_checkpoint.epoch as f64);
+    EPOCHS_SINCE_FINALITY.set(epoch.saturating_sub(state.finalized_checkpoint.epoch) as f64);
+    JUSTIFICATION_BITS.set(state.justification_bits as f64);
+}
+
+fn set_sync_committee_to_metrics(sync_committee_by_range: &SyncCommitteeByRange) {
+    for (range_name, summary) in sync_committee_by_range.iter() {
+        set_gauge(
+            &SYNC_COMMITTEE_MEMBERS,
+            &[range_name, "current"],
+            summary.current_members as f64,
+        );
+        set_gauge(
+            &SYNC_COMMITTEE_MEMBERS,
+            &[range_name, "next"],
+            summary.next_members as f64,
+        );
+        set_gauge(&SYNC_COMMITTEE_DUTIES, &[range_name], summary.duties as f64);
+        // Most groups hold no seat in the committee, they have no participation to export
+        let participation =
+            (summary.duties > 0).then(|| summary.participated as f64 / summary.duties as f64);
+        set_or_remove_gauge(&SYNC_COMMITTEE_PARTICIPATION, &[range_name], participation);
+    }
+}
+
+fn set_proposals_to_metrics(proposals_by_range: &ProposalsByRange) {
+    for (range_name, summary) in proposals_by_range.iter() {
+        inc_counter_by(&PROPOSALS_SCHEDULED, &[range_name], summary.scheduled);
+        inc_counter_by(&PROPOSALS_PROPOSED, &[range_name], summary.proposed);
+        inc_counter_by(&PROPOSALS_MISSED, &[range_name], summary.missed);
+    }
+}
+
+fn set_pending_queues_to_metrics(pending_queues: &PendingQueues) {
+    PENDING_DEPOSITS_UNATTRIBUTED.set(pending_queues.unattributed_deposits as f64);
+    PENDING_DEPOSITS_UNATTRIBUTED_AMOUNT.set(pending_queues.unattributed_deposits_amount as f64);
+    for (range_name, summary) in pending_queues.by_range.iter() {
+        set_gauge(&PENDING_DEPOSITS, &[range_name], summary.deposits as f64);
+        set_gauge(
+            &PENDING_DEPOSITS_AMOUNT,
+            &[range_name],
+            summary.deposits_amount as f64,
+        );
+        set_gauge(
+            &PENDING_PARTIAL_WITHDRAWALS,
+            &[range_name],
+            summary.partial_withdrawals as f64,
+        );
+        set_gauge(
+            &PENDING_PARTIAL_WITHDRAWALS_AMOUNT,
+            &[range_name],
+            summary.partial_withdrawals_amount as f64,
+        );
+        set_gauge(
+            &PENDING_CONSOLIDATIONS,
+            &[range_name, "source"],
+            summary.consolidations_source as f64,
+        );
+        set_gauge(
+            &PENDING_CONSOLIDATIONS,
+            &[range_name, "target"],
+            summary.consolidations_target as f64,
+        );
+        set_gauge(
+            &CONSOLIDATING_VALIDATORS,
+            &[range_name],
+            summary.consolidating_validators as f64,
+        );
     }
 }
 
+/// Empty for groups with no validators to average
+fn fmt_optional(value: Option<f32>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
 fn dump_participation_to_stdout(participation_by_range: &ParticipationByRange) {
     let mut table = Table::new();
     table.set_format(*format::consts::FORMAT_NO_BORDER_LINE_SEPARATOR);
@@ -224,28 +1004,225 @@ fn dump_participation_to_stdout(participation_by_range: &ParticipationByRange) {
         Cell::new("Source"),
         Cell::new("Target"),
         Cell::new("Head"),
+        Cell::new("Balance avg"),
+        Cell::new("Active"),
     ]));
 
     for (range_name, _, summary) in participation_by_range.iter() {
         table.add_row(Row::new(vec![
             Cell::new(range_name),
-            Cell::new(&summary.source_participation_ratio.to_string()),
-            Cell::new(&summary.target_participation_ratio.to_string()),
-            Cell::new(&summary.head_participation_ratio.to_string()),
+            Cell::new(&fmt_optional(summary.source_participation_ratio)),
+            Cell::new(&fmt_optional(summary.target_participation_ratio)),
+            Cell::new(&fmt_optional(summary.head_participation_ratio)),
+            Cell::new(&fmt_optional(summary.balance_avg)),
+            Cell::new(&summary.status_counts[ValidatorStatus::Active as usize].to_string()),
         ]));
     }
 
     table.printstd();
 }
 
-async fn task_fetch_state_every_epoch(
-    genesis: &Genesis,
-    config: &ConfigSpec,
+/// Epochs of proposer duties kept to retry fetching their proposals, older ones are dropped
+const MAX_PENDING_PROPOSER_DUTIES: usize = 8;
+
+/// Counts the proposals of the `pending_duties` whose epoch has ended, keeping the duties whose
+/// proposals failed to fetch to retry on the next call. Adds the proposer duties of
+/// `current_epoch`, to be checked once it has ended.
+async fn process_proposals(
     beacon_url: &str,
     extra_headers: &HeaderMap,
     ranges: &IndexGroups,
+    pending_duties: &mut Vec<EpochProposerDuties>,
+    current_epoch: u64,
+) {
+    for duties in std::mem::take(pending_duties) {
+        // Epoch has not ended yet, keep the duties to not count them twice
+        if duties.epoch >= current_epoch {
+            pending_duties.push(duties);
+            continue;
+        }
+        match fetch_proposals(beacon_url, extra_headers, &duties).await {
+            Err(e) => {
+                error!(
+                    "error fetching proposals of epoch {}, retrying on next fetch: {:?}",
+                    duties.epoch, e
+                );
+                pending_duties.push(duties);
+            }
+            Ok(proposals) => set_proposals_to_metrics(&group_proposals(ranges, &proposals)),
+        }
+    }
+
+    if !pending_duties
+        .iter()
+        .any(|duties| duties.epoch == current_epoch)
+    {
+        match fetch_proposer_duties(beacon_url, extra_headers, current_epoch).await {
+            Err(e) => error!("error fetching proposer duties: {:?}", e),
+            Ok(duties) => pending_duties.push(duties),
+        }
+    }
+
+    if pending_duties.len() > MAX_PENDING_PROPOSER_DUTIES {
+        let dropped = pending_duties.len() - MAX_PENDING_PROPOSER_DUTIES;
+        warn!(
+            "dropping proposer duties of {} epochs never processed",
+            dropped
+        );
+        pending_duties.drain(..dropped);
+    }
+}
+
+/// Optional work done on each fetch besides exporting participation
+struct FetchOptions {
+    /// Dump participation ranges to stdout
     dump: bool,
+    /// Fetch blocks to compute sync committee participation
+    sync_committee: bool,
+    /// Fetch proposer duties and block headers to count proposals
+    proposals: bool,
+    /// Fetch the state from all endpoints to compare them
+    compare: bool,
+}
+
+/// Export the metrics computed from the state alone. Guards against unexpected state data
+/// panicking the background task, which would silently stop metrics from updating
+fn export_state_metrics(
+    ranges: &IndexGroups,
+    pubkey_groups: &PubkeyGroups,
+    state: &StatePartial,
+    config: &ConfigSpec,
+    options: &FetchOptions,
+) -> std::thread::Result<()> {
+    panic::catch_unwind(|| {
+        let participation_by_range = group_target_participation(ranges, state);
+        set_participation_to_metrics(&participation_by_range);
+        if let Err(e) = set_range_labels_to_metrics(ranges, state) {
+            error!("error setting range labels: {:?}", e);
+        }
+        set_finality_to_metrics(state, config);
+        set_participation_epoch_to_metrics(state);
+        if state.fork >= ForkName::Electra {
+            set_pending_queues_to_metrics(&group_pending_queues(ranges, pubkey_groups, state));
+        }
+        if options.dump {
+            dump_participation_to_stdout(&participation_by_range);
+        }
+    })
+}
+
+/// Fetches and processes epoch boundary states, keeping the data needed across epochs
+struct EpochFetcher<'a> {
+    config: &'a ConfigSpec,
+    genesis: &'a Genesis,
+    endpoints: &'a [BeaconEndpoint],
+    /// Latest groups, swapped when the groups files are reloaded
+    groups: watch::Receiver<Arc<Groups>>,
+    options: &'a FetchOptions,
+    /// Groups with pubkeys resolved by the registry of the last state
+    ranges: IndexGroups,
+    /// Proposer duties of epochs whose proposals are not counted yet
+    proposer_duties: Vec<EpochProposerDuties>,
+    /// Follow-up requests go to the endpoint that served the last state
+    endpoint: &'a BeaconEndpoint,
+}
+
+impl<'a> EpochFetcher<'a> {
+    fn new(
+        config: &'a ConfigSpec,
+        genesis: &'a Genesis,
+        endpoints: &'a [BeaconEndpoint],
+        groups: watch::Receiver<Arc<Groups>>,
+        options: &'a FetchOptions,
+    ) -> Self {
+        let ranges = groups.borrow().ranges.clone();
+        EpochFetcher {
+            config,
+            genesis,
+            endpoints,
+            groups,
+            options,
+            ranges,
+            proposer_duties: vec![],
+            endpoint: &endpoints[0],
+        }
+    }
+
+    /// Resolves when the groups are reloaded, never if they are not watched
+    async fn groups_changed(&mut self) {
+        if self.groups.changed().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+
+    /// Fetch the state at `epoch_start_slot` and export all its metrics
+    async fn fetch_epoch(&mut self, epoch_start_slot: u64) {
+        let (config, genesis, options) = (self.config, self.genesis, self.options);
+
+        match fetch_state_from_endpoints(config, genesis, self.endpoints, epoch_start_slot).await {
+            Err(e) => error!("error fetching state: {:?}", e),
+            Ok((serving_endpoint, state)) => {
+                self.endpoint = serving_endpoint;
+                let groups = self.groups.borrow_and_update().clone();
+                let ranges = resolve_groups(&groups, &state);
+                remove_stale_ranges_from_metrics(&self.ranges, &ranges);
+                self.ranges = ranges;
+                let ranges = &self.ranges;
+                if export_state_metrics(ranges, &groups.pubkeys, &state, config, options).is_err() {
+                    error!("error processing state at slot {}", state.slot);
+                }
+
+                if options.compare {
+                    compare_endpoints(config, genesis, self.endpoints, self.endpoint, &state).await;
+                }
+
+                if options.sync_committee {
+                    let previous_epoch = state.participation_epoch;
+                    match fetch_epoch_sync_aggregates(
+                        config,
+                        &self.endpoint.url,
+                        &self.endpoint.headers,
+                        previous_epoch,
+                    )
+                    .await
+                    {
+                        Err(e) => error!("error fetching sync aggregates: {:?}", e),
+                        Ok(aggregates) => {
+                            let sync_committee_by_range = group_sync_committee_participation(
+                                ranges,
+                                &state,
+                                config,
+                                &aggregates,
+                            );
+                            set_sync_committee_to_metrics(&sync_committee_by_range);
+                        }
+                    }
+                }
+            }
+        }
+
+        if options.proposals {
+            process_proposals(
+                &self.endpoint.url,
+                &self.endpoint.headers,
+                &self.ranges,
+                &mut self.proposer_duties,
+                epoch_start_slot / config.slots_per_epoch,
+            )
+            .await;
+        }
+    }
+}
+
+async fn task_fetch_state_every_epoch(
+    genesis: &Genesis,
+    config: &ConfigSpec,
+    endpoints: &[BeaconEndpoint],
+    groups: watch::Receiver<Arc<Groups>>,
+    options: &FetchOptions,
 ) -> Result<()> {
+    let mut fetcher = EpochFetcher::new(config, genesis, endpoints, groups, options);
+
     loop {
         match current_epoch_start_slot(genesis, config) {
             Err(e) => error!("error computing current epoch: {:?}", e),
@@ -254,34 +1231,556 @@ async fn task_fetch_state_every_epoch(
                     info!("before genesis, going to sleep")
                 } else {
                     // Only after genesis
-                    match fetch_epoch_participation(config, beacon_url, extra_headers).await {
-                        Err(e) => error!("error fetching state: {:?}", e),
-                        Ok(state) => {
-                            let participation_by_range = group_target_participation(ranges, &state);
-                            set_participation_to_metrics(&participation_by_range);
-                            if dump {
-                                dump_participation_to_stdout(&participation_by_range);
-                            }
-                        }
-                    }
+                    fetcher.fetch_epoch(slot).await;
                 }
             }
         }
 
-        // Run once on boot, then every interval at end of epoch
+        // Run once on boot, then every interval at end of epoch. Wait one slot into the epoch so
+        // nodes have processed the epoch boundary state instead of serving the previous one.
 
-        time::sleep(to_next_epoch_start(genesis, config).unwrap_or_else(|e| {
+        let to_next_epoch = to_next_epoch_start(genesis, config).unwrap_or_else(|e| {
             error!("error computing to_next_epoch_start: {:?}", e);
             Duration::from_secs(config.seconds_per_slot * config.slots_per_epoch)
-        }))
-        .await;
+        });
+        tokio::select! {
+            _ = time::sleep(to_next_epoch + Duration::from_secs(config.seconds_per_slot)) => {}
+            _ = fetcher.groups_changed() => info!("groups reloaded, fetching the current epoch again"),
+        }
+    }
+}
+
+/// Fetch the head state every `poll_slots` slots and export only its current epoch
+/// participation, so groups failing to attest show up before the epoch ends
+async fn task_poll_current_epoch(
+    genesis: &Genesis,
+    config: &ConfigSpec,
+    endpoints: &[BeaconEndpoint],
+    groups: watch::Receiver<Arc<Groups>>,
+    poll_slots: u64,
+) {
+    let mut poll = time::interval(Duration::from_secs(config.seconds_per_slot * poll_slots));
+    poll.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+    // Ranges of the last export, to remove the series of groups removed by a reload
+    let mut previous_ranges = IndexGroups::new();
+    loop {
+        poll.tick().await;
+        let state = match fetch_from_endpoints(endpoints, "head state", |endpoint| async {
+            fetch_epoch_participation(config, genesis, endpoint, "head")
+                .await?
+                .ok_or_else(|| anyhow!("no head state"))
+        })
+        .await
+        {
+            Ok(state) => state,
+            Err(e) => {
+                error!("error fetching head state: {:?}", e);
+                continue;
+            }
+        };
+        let (ranges, _) = groups.borrow().resolve(&state.validators);
+        remove_stale_ranges_from_metrics(&previous_ranges, &ranges);
+        let result = panic::catch_unwind(|| {
+            let participation_by_range = group_target_participation(&ranges, &state);
+            set_current_participation_to_metrics(&participation_by_range, state.slot);
+        });
+        if result.is_err() {
+            error!("error processing head state at slot {}", state.slot);
+        }
+        previous_ranges = ranges;
+    }
+}
+
+/// Fetch the state on the first head event of each epoch, and again on reorgs crossing the epoch
+/// boundary, instead of relying on the local clock
+async fn task_fetch_state_on_events(
+    genesis: &Genesis,
+    config: &ConfigSpec,
+    endpoints: &[BeaconEndpoint],
+    groups: watch::Receiver<Arc<Groups>>,
+    options: &FetchOptions,
+) -> Result<()> {
+    let (tx, mut rx) = mpsc::channel(64);
+    let subscribe_endpoints = endpoints.to_vec();
+    let slot_duration = Duration::from_secs(config.seconds_per_slot);
+    let epoch_duration = slot_duration * config.slots_per_epoch as u32;
+    tokio::spawn(async move {
+        // Head events are sent every slot, a stream silent for 2 epochs is stalled
+        subscribe_events_with_failover(
+            &subscribe_endpoints,
+            &tx,
+            2 * epoch_duration,
+            slot_duration,
+            epoch_duration,
+        )
+        .await
+    });
+
+    let mut fetcher = EpochFetcher::new(config, genesis, endpoints, groups, options);
+    let mut fetched_epoch: Option<u64> = None;
+
+    loop {
+        let event = tokio::select! {
+            event = rx.recv() => event.ok_or_else(|| anyhow!("events channel closed"))?,
+            _ = fetcher.groups_changed() => {
+                if let Some(epoch) = fetched_epoch {
+                    info!("groups reloaded, fetching epoch {} again", epoch);
+                    fetcher.fetch_epoch(epoch * config.slots_per_epoch).await;
+                }
+                continue;
+            }
+        };
+        match event {
+            BeaconEvent::Head { slot } => {
+                let epoch = slot / config.slots_per_epoch;
+                if fetched_epoch < Some(epoch) {
+                    debug!("first head of epoch {} at slot {}", epoch, slot);
+                    fetcher.fetch_epoch(epoch * config.slots_per_epoch).await;
+                    fetched_epoch = Some(epoch);
+                }
+            }
+            BeaconEvent::ChainReorg { slot, depth } => {
+                CHAIN_REORGS.inc();
+                LAST_CHAIN_REORG_DEPTH.set(depth as f64);
+                if reorg_crosses_epoch(slot, depth, config.slots_per_epoch) {
+                    EPOCH_CROSSING_CHAIN_REORGS.inc();
+                    let epoch = slot / config.slots_per_epoch;
+                    info!(
+                        "reorg of depth {} at slot {} crosses epoch boundary, re-fetching epoch {}",
+                        depth, slot, epoch
+                    );
+                    fetcher.fetch_epoch(epoch * config.slots_per_epoch).await;
+                    fetched_epoch = Some(epoch);
+                }
+            }
+            BeaconEvent::FinalizedCheckpoint { epoch } => {
+                debug!("finalized epoch {}", epoch);
+                FINALIZED_EPOCH.set(epoch as f64);
+            }
+        }
+    }
+}
+
+fn endpoints_from_args(args: &BeaconArgs) -> Result<Vec<BeaconEndpoint>> {
+    let endpoints = parse_endpoints(
+        &args.urls,
+        args.headers.as_deref().unwrap_or_default(),
+        args.url_header.as_deref().unwrap_or_default(),
+    )?;
+    for endpoint in endpoints.iter() {
+        info!(
+            "beacon URL {} extra headers {:?}",
+            endpoint.label(),
+            endpoint.headers.keys().collect::<Vec<_>>()
+        );
+    }
+    Ok(endpoints)
+}
+
+/// Parse groups files mapping index ranges and pubkeys to host names
+async fn load_groups(args: &RangesArgs) -> Result<Groups> {
+    let ranges_str = if let Some(ranges_str) = &args.ranges {
+        Some(ranges_str.clone())
+    } else if let Some(path_or_url) = &args.ranges_file {
+        Some(resolve_path_or_url(path_or_url).await?)
+    } else {
+        None
+    };
+    let pubkeys = match &args.pubkeys_file {
+        Some(path_or_url) => load_pubkey_groups(&resolve_path_or_url(path_or_url).await?)
+            .await
+            .with_context(|| format!("loading pubkeys file {}", path_or_url))?,
+        None => vec![],
+    };
+    let withdrawals = WithdrawalGrouping {
+        names: match &args.withdrawal_addresses_file {
+            Some(path_or_url) => {
+                parse_withdrawal_names(&resolve_path_or_url(path_or_url).await?)
+                    .with_context(|| format!("loading withdrawal addresses file {}", path_or_url))?
+            }
+            None => HashMap::new(),
+        },
+        threshold: args.withdrawal_threshold,
+    };
+    if ranges_str.is_none() && pubkeys.is_empty() && withdrawals.is_empty() {
+        return Err(anyhow!(
+            "Must set --ranges, --ranges-file, a non empty --pubkeys-file or withdrawal grouping"
+        ));
+    }
+
+    let ranges = match ranges_str {
+        Some(ranges_str) => {
+            info!("index ranges ---\n{}\n---", &ranges_str);
+            if args.strict {
+                let errors: Vec<String> = lint_ranges(&ranges_str, None)
+                    .iter()
+                    .filter(|issue| issue.is_error())
+                    .map(|issue| issue.to_string())
+                    .collect();
+                if !errors.is_empty() {
+                    return Err(anyhow!("Invalid ranges:\n{}", errors.join("\n")));
+                }
+            }
+            parse_ranges(&ranges_str)?
+        }
+        None => vec![],
+    };
+    for (name, group_pubkeys) in pubkeys.iter() {
+        info!("pubkey group {} with {} pubkeys", name, group_pubkeys.len());
+    }
+    // Names of all groups files are exported the same way, only the ranges file is linted
+    let names: BTreeSet<&String> = pubkeys
+        .iter()
+        .map(|(name, _)| name)
+        .chain(withdrawals.names.values())
+        .collect();
+    for name in names {
+        let keys = unknown_label_keys(name);
+        if !keys.is_empty() {
+            warn!(
+                "unknown label keys {} of group {}, only {} are exported as labels",
+                keys.join(", "),
+                name,
+                LABEL_KEYS.join(", ")
+            );
+        }
+    }
+    Ok(Groups {
+        ranges,
+        pubkeys,
+        withdrawals,
+    })
+}
+
+/// Remove the series of the ranges of `previous` not in `ranges`, removed from the groups files
+/// or resolved to no group anymore
+fn remove_stale_ranges_from_metrics(previous: &IndexGroups, ranges: &IndexGroups) {
+    for (range_name, _) in previous {
+        if ranges.iter().any(|(name, _)| name == range_name) {
+            continue;
+        }
+        info!("removing metrics of range {}", range_name);
+        let range = range_name.as_str();
+        for gauge in [
+            &*SOURCE_PARTICIPATION,
+            &*TARGET_PARTICIPATION,
+            &*HEAD_PARTICIPATION,
+            &*CURRENT_SOURCE_PARTICIPATION,
+            &*CURRENT_TARGET_PARTICIPATION,
+            &*CURRENT_HEAD_PARTICIPATION,
+            &*SOURCE_PARTICIPATION_BALANCE,
+            &*TARGET_PARTICIPATION_BALANCE,
+            &*HEAD_PARTICIPATION_BALANCE,
+            &*INACTIVITY_SCORES,
+            &*INDEXES_PER_GROUP,
+            &*EXCLUDED_INDEXES_PER_GROUP,
+            &*INDEXES_NOT_IN_REGISTRY_PER_GROUP,
+            &*BALANCE_SUM,
+            &*BALANCE_AVG,
+            &*BALANCE_MIN,
+            &*BALANCE_MAX,
+            &*SYNC_COMMITTEE_PARTICIPATION,
+            &*SYNC_COMMITTEE_DUTIES,
+            &*PENDING_DEPOSITS,
+            &*PENDING_DEPOSITS_AMOUNT,
+            &*PENDING_PARTIAL_WITHDRAWALS,
+            &*PENDING_PARTIAL_WITHDRAWALS_AMOUNT,
+            &*CONSOLIDATING_VALIDATORS,
+            &*UNRESOLVED_PUBKEYS,
+        ] {
+            let _ = gauge.remove_label_values(&[range]);
+        }
+        for counter in [
+            &*PROPOSALS_SCHEDULED,
+            &*PROPOSALS_PROPOSED,
+            &*PROPOSALS_MISSED,
+        ] {
+            let _ = counter.remove_label_values(&[range]);
+        }
+        for status in ValidatorStatus::ALL {
+            let _ = VALIDATORS_BY_STATUS.remove_label_values(&[range, status.as_str()]);
+        }
+        for committee in ["current", "next"] {
+            let _ = SYNC_COMMITTEE_MEMBERS.remove_label_values(&[range, committee]);
+        }
+        for role in ["source", "target"] {
+            let _ = PENDING_CONSOLIDATIONS.remove_label_values(&[range, role]);
+        }
+        // Range labels and their aggregates are reset on each fetch
+    }
+}
+
+/// Index groups of `state`, exporting the count of pubkeys not yet in its registry
+fn resolve_groups(groups: &Groups, state: &StatePartial) -> IndexGroups {
+    let (ranges, unresolved) = groups.resolve(&state.validators);
+    for (name, count) in unresolved {
+        if count > 0 {
+            debug!("group {} has {} pubkeys not in the registry", name, count);
+        }
+        set_gauge(&UNRESOLVED_PUBKEYS, &[&name], count as f64);
+    }
+    ranges
+}
+
+/// Missing ratios and averages of time series outputs, `null` in JSON
+fn nan_if_none(value: Option<f32>) -> f64 {
+    value.map_or(f64::NAN, f64::from)
+}
+
+/// Values of a range summary for time series outputs, named as their gauges
+fn summary_values(indexes: &[usize], summary: &RangeSummary) -> Vec<(&'static str, f64)> {
+    vec![
+        (
+            "source_participation",
+            nan_if_none(summary.source_participation_ratio),
+        ),
+        (
+            "target_participation",
+            nan_if_none(summary.target_participation_ratio),
+        ),
+        (
+            "head_participation",
+            nan_if_none(summary.head_participation_ratio),
+        ),
+        (
+            "source_participation_balance",
+            nan_if_none(summary.source_participation_balance_ratio),
+        ),
+        (
+            "target_participation_balance",
+            nan_if_none(summary.target_participation_balance_ratio),
+        ),
+        (
+            "head_participation_balance",
+            nan_if_none(summary.head_participation_balance_ratio),
+        ),
+        (
+            "inactivity_scores",
+            nan_if_none(summary.inactivity_scores_avg),
+        ),
+        ("indexes_per_group", indexes.len() as f64),
+        (
+            "excluded_indexes_per_group",
+            summary.excluded_indexes as f64,
+        ),
+        (
+            "indexes_not_in_registry_per_group",
+            summary.indexes_not_in_registry as f64,
+        ),
+        ("balance_sum_gwei", summary.balance_sum as f64),
+        ("balance_avg_gwei", nan_if_none(summary.balance_avg)),
+        ("balance_min_gwei", summary.balance_min as f64),
+        ("balance_max_gwei", summary.balance_max as f64),
+    ]
+}
+
+async fn backfill(args: BackfillArgs) -> Result<()> {
+    if args.from_epoch > args.to_epoch {
+        return Err(anyhow!(
+            "--from-epoch {} > --to-epoch {}",
+            args.from_epoch,
+            args.to_epoch
+        ));
+    }
+    let endpoints = endpoints_from_args(&args.beacon)?;
+    let groups = load_groups(&args.ranges).await?;
+    let (genesis, config) = load_chain(&args.network, &endpoints).await?;
+
+    let out: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(BufWriter::new(
+            File::create(path).with_context(|| format!("creating {}", path))?,
+        )),
+        None => Box::new(io::stdout()),
+    };
+    let mut writer = BackfillWriter::new(out, args.format);
+
+    for epoch in args.from_epoch..=args.to_epoch {
+        // The state at the start of the next epoch has the full participation of `epoch`
+        let epoch_start_slot = (epoch + 1) * config.slots_per_epoch;
+        let (_, state) =
+            fetch_state_from_endpoints(&config, &genesis, &endpoints, epoch_start_slot)
+                .await
+                .with_context(|| format!("fetching state of epoch {}", epoch))?;
+        if state.participation_epoch != epoch {
+            warn!(
+                "skipping epoch {}, no state at its end, closest state at slot {}",
+                epoch, state.slot
+            );
+            continue;
+        }
+
+        let (ranges, _) = groups.resolve(&state.validators);
+        let records: Vec<EpochRecord> = group_target_participation(&ranges, &state)
+            .iter()
+            .map(|(range_name, indexes, summary)| EpochRecord {
+                epoch,
+                slot: state.slot,
+                timestamp: genesis.genesis_time + state.slot * config.seconds_per_slot,
+                range: range_name.clone(),
+                values: summary_values(indexes, summary),
+            })
+            .collect();
+        writer.write(&records)?;
+        info!("backfilled epoch {}", epoch);
+    }
+
+    writer.finish()
+}
+
+async fn analyze(args: AnalyzeArgs) -> Result<()> {
+    let config = builtin_config(&args.config)?;
+    let groups = load_groups(&args.ranges).await?;
+    let state_buf =
+        Bytes::from(fs::read(&args.state).with_context(|| format!("reading {}", args.state))?);
+    let state = deserialize_partial_state(&config, &state_buf, args.fork)?;
+    let epoch = state.slot / config.slots_per_epoch;
+    info!(
+        "decoded {} state at slot {} epoch {}",
+        state.fork.as_str(),
+        state.slot,
+        epoch
+    );
+
+    let (ranges, unresolved) = groups.resolve(&state.validators);
+    for (name, count) in unresolved.iter().filter(|(_, count)| *count > 0) {
+        warn!("group {} has {} pubkeys not in the registry", name, count);
+    }
+    let participation_by_range = group_target_participation(&ranges, &state);
+    match args.format {
+        AnalyzeFormat::Table => dump_participation_to_stdout(&participation_by_range),
+        AnalyzeFormat::Json => {
+            let ranges: Vec<serde_json::Value> = participation_by_range
+                .iter()
+                .map(|(range_name, indexes, summary)| {
+                    let mut object = serde_json::Map::new();
+                    object.insert("range".to_owned(), json!(range_name));
+                    for (name, value) in summary_values(indexes, summary) {
+                        object.insert(name.to_owned(), json!(value));
+                    }
+                    for (status, count) in ValidatorStatus::ALL.iter().zip(summary.status_counts) {
+                        object.insert(status.as_str().to_owned(), json!(count));
+                    }
+                    serde_json::Value::Object(object)
+                })
+                .collect();
+            let output = json!({
+                "fork": state.fork.as_str(),
+                "slot": state.slot,
+                "epoch": epoch,
+                "ranges": ranges,
+            });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+    }
+    Ok(())
+}
+
+async fn validate_ranges(args: ValidateRangesArgs) -> Result<()> {
+    let ranges_str = resolve_path_or_url(&args.ranges_file).await?;
+    let validator_count = match (args.validator_count, &args.beacon_url) {
+        (Some(validator_count), _) => Some(validator_count),
+        (None, Some(urls)) => {
+            let endpoints = endpoints_from_args(&BeaconArgs {
+                urls: urls.clone(),
+                headers: args.headers.clone(),
+                url_header: None,
+            })?;
+            let (genesis, config) = load_chain(&args.network, &endpoints).await?;
+            let state = fetch_from_endpoints(&endpoints, "head state", |endpoint| async {
+                fetch_epoch_participation(&config, &genesis, endpoint, "head")
+                    .await?
+                    .ok_or_else(|| anyhow!("no head state"))
+            })
+            .await?;
+            info!(
+                "head state at slot {} has {} validators",
+                state.slot,
+                state.validators.len()
+            );
+            Some(state.validators.len())
+        }
+        (None, None) => None,
+    };
+
+    let issues = lint_ranges(&ranges_str, validator_count);
+    for issue in issues.iter() {
+        let severity = if issue.is_error() { "error" } else { "warning" };
+        println!("{}: {}", severity, issue);
+    }
+    let errors = issues.iter().filter(|issue| issue.is_error()).count();
+    let warnings = issues.len() - errors;
+    println!(
+        "{}: {} errors, {} warnings",
+        args.ranges_file, errors, warnings
+    );
+
+    if errors > 0 || (args.strict && warnings > 0) {
+        return Err(anyhow!("{} is not valid", args.ranges_file));
+    }
+    Ok(())
+}
+
+/// Run `fetch` against each endpoint in priority order until one succeeds
+async fn fetch_from_endpoints<'a, T, F, Fut>(
+    endpoints: &'a [BeaconEndpoint],
+    what: &str,
+    fetch: F,
+) -> Result<T>
+where
+    F: Fn(&'a BeaconEndpoint) -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut last_error = anyhow!("no beacon URLs");
+    for endpoint in endpoints {
+        match fetch(endpoint).await {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                warn!("error fetching {} from {}: {:?}", what, endpoint.label(), e);
+                last_error = e;
+            }
+        }
     }
+    Err(last_error)
+}
+
+/// Genesis and config of the network args, fetched from the first endpoint that responds if not
+/// set
+async fn load_chain(
+    args: &NetworkArgs,
+    endpoints: &[BeaconEndpoint],
+) -> Result<(Genesis, ConfigSpec)> {
+    let (config, genesis) = if let Some(network) = &args.network {
+        builtin_network(network)?
+    } else if let Some(config_path) = &args.config_file {
+        let preset_paths = args.preset_file.as_deref().unwrap_or_default();
+        (load_config_yaml(config_path, preset_paths).await?, None)
+    } else {
+        let config = fetch_from_endpoints(endpoints, "chain config", |endpoint| {
+            fetch_config(&endpoint.url, &endpoint.headers)
+        })
+        .await
+        .context("fetch_config")?;
+        if let Some(network) = check_builtin_preset(&config)? {
+            info!("fetched config matches the {} preset", network);
+        }
+        (config, None)
+    };
+
+    let genesis = match (args.genesis_time, genesis) {
+        (Some(genesis_time), _) => Genesis { genesis_time },
+        (None, Some(genesis)) => genesis,
+        (None, None) => fetch_from_endpoints(endpoints, "genesis", |endpoint| {
+            fetch_genesis(&endpoint.url, &endpoint.headers)
+        })
+        .await
+        .context("fetch_genesis")?,
+    };
+    Ok((genesis, config))
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
-    let beacon_url = cli.url.clone();
 
     let level = if cli.verbose {
         log::LevelFilter::Debug
@@ -291,56 +1790,67 @@ async fn main() -> Result<()> {
 
     env_logger::Builder::new().filter_level(level).init();
 
-    info!("connecting to beacon URL {:?}", beacon_url);
+    if let Some(command) = cli.command {
+        return match command {
+            Command::Backfill(args) => backfill(args).await,
+            Command::Analyze(args) => analyze(args).await,
+            Command::ValidateRanges(args) => validate_ranges(args).await,
+        };
+    }
 
-    let mut extra_headers = HeaderMap::new();
-    if let Some(headers_str) = cli.headers {
-        for header_str in headers_str {
-            let parts: Vec<&str> = header_str.split(':').collect();
-            if parts.len() != 2 {
-                return Err(anyhow!("Invalid header: {}", header_str));
-            }
+    let endpoints = endpoints_from_args(&cli.beacon)?;
+    let groups = load_groups(&cli.ranges).await?;
+    set_reload_to_metrics(true);
+    let (groups_tx, groups_rx) = watch::channel(Arc::new(groups));
 
-            let name = HeaderName::from_str(parts[0])?;
-            let value = parts[1].trim().parse()?;
-            extra_headers.insert(name, value);
-        }
-        info!("extra headers {:?}", extra_headers);
+    let reload_sources: Vec<String> = [
+        &cli.ranges.ranges_file,
+        &cli.ranges.pubkeys_file,
+        &cli.ranges.withdrawal_addresses_file,
+    ]
+    .into_iter()
+    .flatten()
+    .cloned()
+    .collect();
+    if !reload_sources.is_empty() {
+        let ranges_args = cli.ranges.clone();
+        let poll_interval = Duration::from_secs(cli.ranges_reload_interval);
+        tokio::spawn(async move {
+            let load = || load_groups(&ranges_args);
+            if let Err(e) =
+                reload_groups_on_change(&reload_sources, poll_interval, load, groups_tx).await
+            {
+                error!("error watching groups files, reload disabled: {:?}", e);
+            }
+        });
     }
 
-    // Parse groups file mapping index ranges to host names
-    let ranges_str = if let Some(ranges_str) = &cli.ranges {
-        ranges_str.clone()
-    } else if let Some(path_or_url) = &cli.ranges_file {
-        resolve_path_or_url(path_or_url).await?
-    } else {
-        return Err(anyhow!("Must set --groups or --groups_file"));
-    };
-    let ranges = parse_ranges(&ranges_str)?;
-    info!("index ranges ---\n{}\n---", &ranges_str);
-
-    let genesis = fetch_genesis(&beacon_url, &extra_headers)
-        .await
-        .context("fetch_genesis")?;
+    let (genesis, config) = load_chain(&cli.network, &endpoints).await?;
     info!("beacon genesis {:?}", genesis);
-
-    let config = fetch_config(&beacon_url, &extra_headers)
-        .await
-        .context("fetch_config")?;
     info!("beacon config {:?}", config);
 
+    if let Some(poll_slots) = cli.current_epoch_poll_slots {
+        let (genesis, config, endpoints) = (genesis.clone(), config.clone(), endpoints.clone());
+        let groups_rx = groups_rx.clone();
+        tokio::spawn(async move {
+            task_poll_current_epoch(&genesis, &config, &endpoints, groups_rx, poll_slots).await
+        });
+    }
+
     // Background task fetching state every interval and registering participation
     // in metrics with provided index ranges
     tokio::spawn(async move {
-        task_fetch_state_every_epoch(
-            &genesis,
-            &config,
-            &beacon_url,
-            &extra_headers,
-            &ranges,
-            cli.dump,
-        )
-        .await
+        let options = FetchOptions {
+            dump: cli.dump,
+            sync_committee: cli.sync_committee,
+            proposals: cli.proposals,
+            compare: cli.compare,
+        };
+        if cli.events {
+            task_fetch_state_on_events(&genesis, &config, &endpoints, groups_rx, &options).await
+        } else {
+            task_fetch_state_every_epoch(&genesis, &config, &endpoints, groups_rx, &options).await
+        }
     });
 
     // Start metrics server
@@ -357,3 +1867,328 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proposals::ProposerDuty;
+    use ssz_state::{Checkpoint, FAR_FUTURE_EPOCH};
+    use sync_committee::SyncCommitteeSummary;
+
+    const EPOCH: u64 = 10;
+
+    fn validator(activation_epoch: u64, effective_balance: u64) -> Validator {
+        Validator {
+            pubkey: [0; 48],
+            withdrawal_credentials: [0; 32],
+            effective_balance,
+            slashed: false,
+            activation_eligibility_epoch: 0,
+            activation_epoch,
+            exit_epoch: FAR_FUTURE_EPOCH,
+            withdrawable_epoch: FAR_FUTURE_EPOCH,
+        }
+    }
+
+    /// State at the first slot of `EPOCH` where all validators attested timely
+    fn state(config: &ConfigSpec, validators: Vec<Validator>) -> StatePartial {
+        let checkpoint = Checkpoint {
+            epoch: 0,
+            root: [0; 32],
+        };
+        let all_flags = TIMELY_SOURCE | TIMELY_TARGET | TIMELY_HEAD;
+        StatePartial {
+            fork: ForkName::Deneb,
+            genesis_time: 0,
+            slot: EPOCH * config.slots_per_epoch,
+            participation_epoch: EPOCH - 1,
+            balances: vec![32_000_000_000; validators.len()],
+            previous_epoch_participation: vec![all_flags; validators.len()],
+            current_epoch_participation: vec![all_flags; validators.len()],
+            inactivity_scores: vec![0; validators.len()],
+            validators,
+            justification_bits: 0,
+            previous_justified_checkpoint: checkpoint.clone(),
+            current_justified_checkpoint: checkpoint.clone(),
+            finalized_checkpoint: checkpoint,
+            current_sync_committee: vec![],
+            next_sync_committee: vec![],
+            pending_deposits: vec![],
+            pending_partial_withdrawals: vec![],
+            pending_consolidations: vec![],
+        }
+    }
+
+    #[test]
+    fn group_participation_without_eligible_validators() {
+        let config = builtin_config("mainnet").unwrap();
+        let state = state(
+            &config,
+            vec![
+                validator(0, 32_000_000_000),
+                validator(EPOCH + 5, 32_000_000_000),
+            ],
+        );
+        let index_groups: IndexGroups = vec![
+            ("active".to_owned(), vec![0]),
+            ("pending".to_owned(), vec![1]),
+            ("not deposited".to_owned(), vec![5, 6]),
+        ];
+        let participation = group_target_participation(&index_groups, &state);
+
+        let (_, _, active) = &participation[0];
+        assert_eq!(active.target_participation_ratio, Some(1.0));
+        assert_eq!(active.target_participation_balance_ratio, Some(1.0));
+        assert_eq!(active.balance_avg, Some(32_000_000_000.0));
+
+        let (_, _, pending) = &participation[1];
+        assert_eq!(pending.target_participation_ratio, None);
+        assert_eq!(pending.current_target_participation_ratio, None);
+        assert_eq!(pending.target_participation_balance_ratio, None);
+        // Balances of pending validators are still known
+        assert_eq!(pending.balance_avg, Some(32_000_000_000.0));
+
+        let (_, _, not_deposited) = &participation[2];
+        assert_eq!(not_deposited.target_participation_ratio, None);
+        assert_eq!(not_deposited.target_participation_balance_ratio, None);
+        assert_eq!(not_deposited.inactivity_scores_avg, None);
+        assert_eq!(not_deposited.balance_avg, None);
+        assert_eq!(not_deposited.indexes_not_in_registry, 2);
+
+        set_participation_to_metrics(&participation);
+        let exported_ranges: Vec<String> = prometheus::gather()
+            .iter()
+            .filter(|family| family.get_name() == "beacon_network_target_participation")
+            .flat_map(|family| family.get_metric().to_vec())
+            .flat_map(|metric| metric.get_label().to_vec())
+            .map(|label| label.get_value().to_owned())
+            .collect();
+        assert!(exported_ranges.contains(&"active".to_owned()));
+        assert!(!exported_ranges.contains(&"pending".to_owned()));
+        assert!(!exported_ranges.contains(&"not deposited".to_owned()));
+    }
+
+    #[test]
+    fn participation_balance_avg_zero_balance() {
+        let validators = vec![validator(0, 0), validator(0, 0)];
+        assert_eq!(
+            participation_balance_avg(&[TIMELY_TARGET; 2], &validators, &[0, 1], TIMELY_TARGET),
+            None
+        );
+    }
+
+    #[test]
+    fn group_participation_clips_indexes_not_in_registry() {
+        let config = builtin_config("mainnet").unwrap();
+        let state = state(
+            &config,
+            vec![
+                validator(0, 32_000_000_000),
+                validator(EPOCH + 5, 32_000_000_000),
+            ],
+        );
+        let index_groups: IndexGroups = vec![("partly deposited".to_owned(), vec![0, 1, 2, 3])];
+        let participation = group_target_participation(&index_groups, &state);
+
+        let (_, indexes, summary) = &participation[0];
+        assert_eq!(indexes.len(), 4);
+        assert_eq!(summary.indexes_not_in_registry, 2);
+        // Only the pending validator is excluded, indexes not in registry are not counted twice
+        assert_eq!(summary.excluded_indexes, 1);
+        assert_eq!(summary.target_participation_ratio, Some(1.0));
+        assert_eq!(summary.balance_sum, 64_000_000_000);
+
+        set_participation_to_metrics(&participation);
+        let gauge_value = |gauge: &prometheus::GaugeVec| {
+            gauge
+                .get_metric_with_label_values(&["partly deposited"])
+                .unwrap()
+                .get()
+        };
+        assert_eq!(gauge_value(&INDEXES_NOT_IN_REGISTRY_PER_GROUP), 2.0);
+        assert_eq!(gauge_value(&EXCLUDED_INDEXES_PER_GROUP), 1.0);
+        assert_eq!(gauge_value(&INDEXES_PER_GROUP), 4.0);
+    }
+
+    #[test]
+    fn export_state_metrics_survives_panics() {
+        let config = builtin_config("mainnet").unwrap();
+        let options = FetchOptions {
+            dump: false,
+            sync_committee: false,
+            proposals: false,
+            compare: false,
+        };
+        let mut state = state(&config, vec![validator(0, 32_000_000_000)]);
+        let index_groups: IndexGroups = vec![("a".to_owned(), vec![0])];
+        assert!(export_state_metrics(&index_groups, &vec![], &state, &config, &options).is_ok());
+
+        // Participation shorter than the registry indexes out of bounds
+        state.previous_epoch_participation.clear();
+        assert!(export_state_metrics(&index_groups, &vec![], &state, &config, &options).is_err());
+    }
+
+    #[test]
+    fn current_participation_gauges() {
+        let config = builtin_config("mainnet").unwrap();
+        let mut state = state(&config, vec![validator(0, 32_000_000_000); 4]);
+        state.slot += 7;
+        state.current_epoch_participation = vec![TIMELY_TARGET, TIMELY_TARGET, 0, 0];
+        let index_groups: IndexGroups = vec![("mid-epoch".to_owned(), vec![0, 1, 2, 3])];
+        let participation = group_target_participation(&index_groups, &state);
+
+        set_current_participation_to_metrics(&participation, state.slot);
+        let current_target = || {
+            CURRENT_TARGET_PARTICIPATION
+                .get_metric_with_label_values(&["mid-epoch"])
+                .unwrap()
+                .get()
+        };
+        assert_eq!(CURRENT_PARTICIPATION_SLOT.get(), state.slot as f64);
+        assert_eq!(current_target(), 0.5);
+
+        // The state of the next epoch boundary has no current epoch participation yet
+        let mid_epoch_slot = state.slot;
+        state.slot += config.slots_per_epoch - 7;
+        state.participation_epoch += 1;
+        state.previous_epoch_participation = std::mem::take(&mut state.current_epoch_participation);
+        state.current_epoch_participation = vec![0; 4];
+        let options = FetchOptions {
+            dump: false,
+            sync_committee: false,
+            proposals: false,
+            compare: false,
+        };
+        export_state_metrics(&index_groups, &vec![], &state, &config, &options).unwrap();
+        assert_eq!(CURRENT_PARTICIPATION_SLOT.get(), mid_epoch_slot as f64);
+        assert_eq!(current_target(), 0.5);
+    }
+
+    #[test]
+    fn remove_current_participation_of_removed_ranges() {
+        let config = builtin_config("mainnet").unwrap();
+        let state = state(&config, vec![validator(0, 32_000_000_000); 2]);
+        let previous: IndexGroups = vec![
+            ("poll kept".to_owned(), vec![0]),
+            ("poll removed".to_owned(), vec![1]),
+        ];
+        set_current_participation_to_metrics(
+            &group_target_participation(&previous, &state),
+            state.slot,
+        );
+
+        let ranges: IndexGroups = vec![("poll kept".to_owned(), vec![0, 1])];
+        remove_stale_ranges_from_metrics(&previous, &ranges);
+        let exported_ranges: Vec<String> = prometheus::gather()
+            .iter()
+            .filter(|family| family.get_name() == "beacon_network_current_target_participation")
+            .flat_map(|family| family.get_metric().to_vec())
+            .map(|metric| metric.get_label()[0].get_value().to_owned())
+            .filter(|range| range.starts_with("poll"))
+            .collect();
+        assert_eq!(exported_ranges, vec!["poll kept"]);
+    }
+
+    #[test]
+    fn sync_committee_participation_without_duties() {
+        let summary = |duties, participated| SyncCommitteeSummary {
+            current_members: duties / 32,
+            next_members: 0,
+            duties,
+            participated,
+        };
+        set_sync_committee_to_metrics(&vec![
+            ("sync member".to_owned(), summary(64, 48)),
+            ("no seat".to_owned(), summary(0, 0)),
+        ]);
+        let participation = |range: &str| {
+            prometheus::gather()
+                .iter()
+                .filter(|family| family.get_name() == "beacon_network_sync_committee_participation")
+                .flat_map(|family| family.get_metric().to_vec())
+                .find(|metric| metric.get_label()[0].get_value() == range)
+                .map(|metric| metric.get_gauge().get_value())
+        };
+        assert_eq!(participation("sync member"), Some(0.75));
+        assert_eq!(participation("no seat"), None);
+        assert_eq!(
+            SYNC_COMMITTEE_DUTIES
+                .get_metric_with_label_values(&["no seat"])
+                .unwrap()
+                .get(),
+            0.0
+        );
+    }
+
+    #[tokio::test]
+    async fn process_proposals_keeps_duties_on_errors() {
+        let duties = |epoch: u64| EpochProposerDuties {
+            epoch,
+            duties: vec![ProposerDuty {
+                slot: epoch * 32,
+                validator_index: 0,
+            }],
+        };
+        let ranges: IndexGroups = vec![("a".to_owned(), vec![0])];
+        // Nothing listens on port 1, all requests fail
+        let beacon_url = "http://127.0.0.1:1";
+
+        let mut pending_duties = vec![duties(5), duties(6)];
+        process_proposals(
+            beacon_url,
+            &HeaderMap::new(),
+            &ranges,
+            &mut pending_duties,
+            6,
+        )
+        .await;
+        assert_eq!(
+            pending_duties
+                .iter()
+                .map(|duties| duties.epoch)
+                .collect::<Vec<_>>(),
+            vec![5, 6]
+        );
+
+        let mut pending_duties: Vec<EpochProposerDuties> = (0..10).map(duties).collect();
+        process_proposals(
+            beacon_url,
+            &HeaderMap::new(),
+            &ranges,
+            &mut pending_duties,
+            20,
+        )
+        .await;
+        assert_eq!(pending_duties.len(), MAX_PENDING_PROPOSER_DUTIES);
+        assert_eq!(pending_duties[0].epoch, 2);
+    }
+
+    #[test]
+    fn rotate_state_before_missed_epoch_boundary() {
+        let config = builtin_config("mainnet").unwrap();
+        let epoch_start_slot = EPOCH * config.slots_per_epoch;
+        // Last state before a missed boundary slot, half of the epoch attested the target
+        let mut state = state(&config, vec![validator(0, 32_000_000_000); 4]);
+        state.slot = epoch_start_slot - 1;
+        state.participation_epoch = EPOCH - 2;
+        state.previous_epoch_participation = vec![0; 4];
+        state.current_epoch_participation = vec![TIMELY_TARGET, TIMELY_TARGET, 0, 0];
+
+        rotate_to_epoch_boundary(&mut state, &config, epoch_start_slot);
+        // Balances and registry are still of the fetched slot
+        assert_eq!(state.slot, epoch_start_slot - 1);
+        assert_eq!(state.participation_epoch, EPOCH - 1);
+        assert_eq!(state.current_epoch_participation, vec![0; 4]);
+        let index_groups: IndexGroups = vec![("a".to_owned(), vec![0, 1, 2, 3])];
+        let (_, _, summary) = &group_target_participation(&index_groups, &state)[0];
+        assert_eq!(summary.target_participation_ratio, Some(0.5));
+        assert_eq!(summary.current_target_participation_ratio, Some(0.0));
+
+        // The state at the boundary is not rotated again
+        rotate_to_epoch_boundary(&mut state, &config, epoch_start_slot);
+        assert_eq!(
+            state.previous_epoch_participation,
+            vec![TIMELY_TARGET, TIMELY_TARGET, 0, 0]
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use anyhow::Result;
 use lazy_static::lazy_static;
-use prometheus::GaugeVec;
+use prometheus::{Gauge, GaugeVec, IntCounter, IntCounterVec};
 
 lazy_static! {
     pub static ref SOURCE_PARTICIPATION: GaugeVec = try_create_gauge_vec(
@@ -26,6 +26,61 @@ lazy_static! {
     )
     .unwrap();
 }
+lazy_static! {
+    pub static ref CURRENT_SOURCE_PARTICIPATION: GaugeVec = try_create_gauge_vec(
+        "beacon_network_current_source_participation",
+        "Source participation in current epoch so far, as of beacon_network_current_participation_slot, by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref CURRENT_TARGET_PARTICIPATION: GaugeVec = try_create_gauge_vec(
+        "beacon_network_current_target_participation",
+        "Target participation in current epoch so far, as of beacon_network_current_participation_slot, by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref CURRENT_HEAD_PARTICIPATION: GaugeVec = try_create_gauge_vec(
+        "beacon_network_current_head_participation",
+        "Head participation in current epoch so far, as of beacon_network_current_participation_slot, by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref CURRENT_PARTICIPATION_SLOT: Gauge = try_create_gauge(
+        "beacon_network_current_participation_slot",
+        "Slot of the state of the current epoch participation gauges"
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref SOURCE_PARTICIPATION_BALANCE: GaugeVec = try_create_gauge_vec(
+        "beacon_network_source_participation_balance",
+        "Source participation in previous epoch weighted by effective balance by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref TARGET_PARTICIPATION_BALANCE: GaugeVec = try_create_gauge_vec(
+        "beacon_network_target_participation_balance",
+        "Target participation in previous epoch weighted by effective balance by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref HEAD_PARTICIPATION_BALANCE: GaugeVec = try_create_gauge_vec(
+        "beacon_network_head_participation_balance",
+        "Head participation in previous epoch weighted by effective balance by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
 lazy_static! {
     pub static ref INACTIVITY_SCORES: GaugeVec = try_create_gauge_vec(
         "beacon_network_inactivity_scores",
@@ -42,6 +97,360 @@ lazy_static! {
     )
     .unwrap();
 }
+lazy_static! {
+    pub static ref BALANCE_SUM: GaugeVec = try_create_gauge_vec(
+        "beacon_network_balance_sum_gwei",
+        "Sum of validator balances in Gwei by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref BALANCE_AVG: GaugeVec = try_create_gauge_vec(
+        "beacon_network_balance_avg_gwei",
+        "Average validator balance in Gwei by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref BALANCE_MIN: GaugeVec = try_create_gauge_vec(
+        "beacon_network_balance_min_gwei",
+        "Min validator balance in Gwei by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref BALANCE_MAX: GaugeVec = try_create_gauge_vec(
+        "beacon_network_balance_max_gwei",
+        "Max validator balance in Gwei by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref VALIDATORS_BY_STATUS: GaugeVec = try_create_gauge_vec(
+        "beacon_network_validators_by_status",
+        "Count of validators per status (pending, active, exiting, exited, slashed) by pre-defined named ranges",
+        &["range", "status"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref EXCLUDED_INDEXES_PER_GROUP: GaugeVec = try_create_gauge_vec(
+        "beacon_network_excluded_indexes_per_group",
+        "Count of indexes in a labeled group not active in previous epoch, excluded from participation ratios",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref INDEXES_NOT_IN_REGISTRY_PER_GROUP: GaugeVec = try_create_gauge_vec(
+        "beacon_network_indexes_not_in_registry_per_group",
+        "Count of indexes in a labeled group beyond the current validator count, not yet in registry",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref SYNC_COMMITTEE_PARTICIPATION: GaugeVec = try_create_gauge_vec(
+        "beacon_network_sync_committee_participation",
+        "Sync committee participation in previous epoch blocks by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref SYNC_COMMITTEE_DUTIES: GaugeVec = try_create_gauge_vec(
+        "beacon_network_sync_committee_duties",
+        "Count of sync committee duties in previous epoch blocks by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref SYNC_COMMITTEE_MEMBERS: GaugeVec = try_create_gauge_vec(
+        "beacon_network_sync_committee_members",
+        "Count of positions in the current and next sync committee by pre-defined named ranges",
+        &["range", "committee"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref PROPOSALS_SCHEDULED: IntCounterVec = try_create_int_counter_vec(
+        "beacon_network_proposals_scheduled_total",
+        "Count of block proposals scheduled by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref PROPOSALS_PROPOSED: IntCounterVec = try_create_int_counter_vec(
+        "beacon_network_proposals_proposed_total",
+        "Count of block proposals included in the canonical chain by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref PROPOSALS_MISSED: IntCounterVec = try_create_int_counter_vec(
+        "beacon_network_proposals_missed_total",
+        "Count of block proposals missed or orphaned by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref PENDING_DEPOSITS: GaugeVec = try_create_gauge_vec(
+        "beacon_network_pending_deposits",
+        "Count of pending deposits to validators by pre-defined named ranges, new validators by pubkey groups",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref PENDING_DEPOSITS_AMOUNT: GaugeVec = try_create_gauge_vec(
+        "beacon_network_pending_deposits_amount",
+        "Sum of pending deposits to validators in Gwei by pre-defined named ranges, new validators by pubkey groups",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref PENDING_DEPOSITS_UNATTRIBUTED: Gauge = try_create_gauge(
+        "beacon_network_pending_deposits_unattributed",
+        "Count of pending deposits of new validators not in any pubkey group"
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref PENDING_DEPOSITS_UNATTRIBUTED_AMOUNT: Gauge = try_create_gauge(
+        "beacon_network_pending_deposits_unattributed_amount",
+        "Sum of pending deposits of new validators not in any pubkey group in Gwei"
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref PENDING_PARTIAL_WITHDRAWALS: GaugeVec = try_create_gauge_vec(
+        "beacon_network_pending_partial_withdrawals",
+        "Count of pending partial withdrawals by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref PENDING_PARTIAL_WITHDRAWALS_AMOUNT: GaugeVec = try_create_gauge_vec(
+        "beacon_network_pending_partial_withdrawals_amount",
+        "Sum of pending partial withdrawals in Gwei by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref PENDING_CONSOLIDATIONS: GaugeVec = try_create_gauge_vec(
+        "beacon_network_pending_consolidations",
+        "Count of pending consolidations with the source or target validator by pre-defined named ranges",
+        &["range", "role"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref CONSOLIDATING_VALIDATORS: GaugeVec = try_create_gauge_vec(
+        "beacon_network_consolidating_validators",
+        "Count of validators in a pending consolidation as source or target by pre-defined named ranges",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref FETCH_ENDPOINT: GaugeVec = try_create_gauge_vec(
+        "beacon_network_fetch_endpoint",
+        "Set to 1 for the beacon endpoint that served the last state fetch, 0 for the others",
+        &["endpoint"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref ENDPOINT_FETCHES: IntCounterVec = try_create_int_counter_vec(
+        "beacon_network_endpoint_fetches_total",
+        "Count of state fetches served by each beacon endpoint",
+        &["endpoint"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref DECODE_ERRORS: IntCounterVec = try_create_int_counter_vec(
+        "beacon_network_decode_errors_total",
+        "Count of fetched states that failed sanity checks against the config and genesis, by beacon endpoint",
+        &["endpoint"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref COMPARE_STATE_SLOT: GaugeVec = try_create_gauge_vec(
+        "beacon_network_compare_state_slot",
+        "Slot of the state served by each beacon node in compare mode",
+        &["node"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref COMPARE_PARTICIPATION_DIFF: GaugeVec = try_create_gauge_vec(
+        "beacon_network_compare_participation_diff",
+        "Count of validators with participation flags different from the reference node, by node and epoch",
+        &["node", "epoch"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref COMPARE_INACTIVITY_SCORES_DIFF: GaugeVec = try_create_gauge_vec(
+        "beacon_network_compare_inactivity_scores_diff",
+        "Count of validators with inactivity score different from the reference node, by node",
+        &["node"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref COMPARE_BALANCES_DIFF: GaugeVec = try_create_gauge_vec(
+        "beacon_network_compare_balances_diff",
+        "Count of validators with balance different from the reference node, by node",
+        &["node"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref COMPARE_FETCH_ERRORS: IntCounterVec = try_create_int_counter_vec(
+        "beacon_network_compare_fetch_errors_total",
+        "Count of errors fetching the state to compare, by node",
+        &["node"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref PARTICIPATION_EPOCH: Gauge = try_create_gauge(
+        "beacon_network_participation_epoch",
+        "Epoch described by the previous epoch participation gauges"
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref STATE_SLOT: Gauge = try_create_gauge(
+        "beacon_network_state_slot",
+        "Slot of the last fetched state"
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref UNRESOLVED_PUBKEYS: GaugeVec = try_create_gauge_vec(
+        "beacon_network_unresolved_pubkeys",
+        "Count of pubkeys of each pubkey group not yet in the validators registry",
+        &["range"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref RANGES_RELOAD_SUCCESS: Gauge = try_create_gauge(
+        "beacon_network_ranges_reload_success",
+        "1 if the last load of the ranges, pubkeys and withdrawal addresses files succeeded, else 0"
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref RANGES_LAST_RELOAD_TIMESTAMP: Gauge = try_create_gauge(
+        "beacon_network_ranges_last_reload_timestamp",
+        "Unix time in seconds of the last successful load of the ranges files"
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref RANGE_LABELS: GaugeVec = try_create_gauge_vec(
+        "beacon_network_range_labels",
+        "Labels of each range with a structured name, always 1. Join on range to aggregate range gauges by label, e.g. `* on(range) group_left(entity) beacon_network_range_labels`",
+        &["range", "entity", "cl", "el", "node"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref ENTITY_PARTICIPATION: GaugeVec = try_create_gauge_vec(
+        "beacon_network_entity_participation",
+        "Participation ratio of the previous epoch of all ranges of an entity, by flag",
+        &["entity", "flag"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref CL_PARTICIPATION: GaugeVec = try_create_gauge_vec(
+        "beacon_network_cl_participation",
+        "Participation ratio of the previous epoch of all ranges of a consensus client, by flag",
+        &["cl", "flag"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref EL_PARTICIPATION: GaugeVec = try_create_gauge_vec(
+        "beacon_network_el_participation",
+        "Participation ratio of the previous epoch of all ranges of an execution client, by flag",
+        &["el", "flag"]
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref FINALIZED_EPOCH: Gauge = try_create_gauge(
+        "beacon_network_finalized_epoch",
+        "Finalized checkpoint epoch"
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref CURRENT_JUSTIFIED_EPOCH: Gauge = try_create_gauge(
+        "beacon_network_current_justified_epoch",
+        "Current justified checkpoint epoch"
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref PREVIOUS_JUSTIFIED_EPOCH: Gauge = try_create_gauge(
+        "beacon_network_previous_justified_epoch",
+        "Previous justified checkpoint epoch"
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref EPOCHS_SINCE_FINALITY: Gauge = try_create_gauge(
+        "beacon_network_epochs_since_finality",
+        "Count of epochs between the state epoch and the finalized checkpoint epoch"
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref JUSTIFICATION_BITS: Gauge = try_create_gauge(
+        "beacon_network_justification_bits",
+        "Justification bitfield of the last 4 epochs as an integer, bit 0 is the current epoch"
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref CHAIN_REORGS: IntCounter = try_create_int_counter(
+        "beacon_network_chain_reorgs_total",
+        "Count of chain reorg events received in events mode"
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref EPOCH_CROSSING_CHAIN_REORGS: IntCounter = try_create_int_counter(
+        "beacon_network_epoch_crossing_chain_reorgs_total",
+        "Count of chain reorg events crossing an epoch boundary received in events mode"
+    )
+    .unwrap();
+}
+lazy_static! {
+    pub static ref LAST_CHAIN_REORG_DEPTH: Gauge = try_create_gauge(
+        "beacon_network_last_chain_reorg_depth",
+        "Depth in slots of the last chain reorg event received in events mode"
+    )
+    .unwrap();
+}
 
 /// Attempts to create a `GaugeVec`, returning `Err` if the registry does not accept the gauge
 /// (potentially due to naming conflict).
@@ -52,6 +461,35 @@ fn try_create_gauge_vec(name: &str, help: &str, label_names: &[&str]) -> Result<
     Ok(counter_vec)
 }
 
+/// Attempts to create a `Gauge`, returning `Err` if the registry does not accept the gauge
+/// (potentially due to naming conflict).
+fn try_create_gauge(name: &str, help: &str) -> Result<Gauge> {
+    let gauge = Gauge::new(name, help)?;
+    prometheus::register(Box::new(gauge.clone()))?;
+    Ok(gauge)
+}
+
+/// Attempts to create an `IntCounter`, returning `Err` if the registry does not accept the counter
+/// (potentially due to naming conflict).
+fn try_create_int_counter(name: &str, help: &str) -> Result<IntCounter> {
+    let counter = IntCounter::new(name, help)?;
+    prometheus::register(Box::new(counter.clone()))?;
+    Ok(counter)
+}
+
+/// Attempts to create an `IntCounterVec`, returning `Err` if the registry does not accept the
+/// counter (potentially due to naming conflict).
+fn try_create_int_counter_vec(
+    name: &str,
+    help: &str,
+    label_names: &[&str],
+) -> Result<IntCounterVec> {
+    let opts = prometheus::Opts::new(name, help);
+    let counter_vec = IntCounterVec::new(opts, label_names)?;
+    prometheus::register(Box::new(counter_vec.clone()))?;
+    Ok(counter_vec)
+}
+
 /// If `gauge_vec.is_ok()`, sets the gauge with the given `name` to the given `value`
 /// otherwise returns false.
 pub fn set_gauge(gauge_vec: &GaugeVec, name: &[&str], value: f64) -> bool {
@@ -63,3 +501,27 @@ pub fn set_gauge(gauge_vec: &GaugeVec, name: &[&str], value: f64) -> bool {
         })
         .unwrap_or_else(|_| false)
 }
+
+/// Sets the gauge with the given label values to `value`, or removes its series if None, e.g.
+/// for ratios of groups with no eligible validators. Returns false on label mismatch.
+pub fn set_or_remove_gauge(gauge_vec: &GaugeVec, name: &[&str], value: Option<f64>) -> bool {
+    match value {
+        Some(value) => set_gauge(gauge_vec, name, value),
+        None => {
+            let _ = gauge_vec.remove_label_values(name);
+            true
+        }
+    }
+}
+
+/// Increments the counter with the given label values by `value`, returns false if the label
+/// values do not match the counter label names.
+pub fn inc_counter_by(counter_vec: &IntCounterVec, name: &[&str], value: u64) -> bool {
+    counter_vec
+        .get_metric_with_label_values(name)
+        .map(|v| {
+            v.inc_by(value);
+            true
+        })
+        .unwrap_or_else(|_| false)
+}
@@ -0,0 +1,258 @@
+use crate::pubkeys::PubkeyGroups;
+use crate::ranges::IndexGroups;
+use crate::ssz_state::{BlsPubkey, StatePartial};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct PendingQueuesSummary {
+    /// Count of pending deposits to validators of the group. Deposits of new validators have no
+    /// index yet, they are attributed by the pubkeys of the group's pubkey group
+    pub deposits: usize,
+    /// Sum of pending deposit amounts in Gwei
+    pub deposits_amount: u64,
+    pub partial_withdrawals: usize,
+    /// Sum of pending partial withdrawal amounts in Gwei
+    pub partial_withdrawals_amount: u64,
+    /// Count of pending consolidations with the source validator in the group
+    pub consolidations_source: usize,
+    /// Count of pending consolidations with the target validator in the group
+    pub consolidations_target: usize,
+    /// Count of distinct validators of the group in a pending consolidation, as source or target
+    pub consolidating_validators: usize,
+}
+
+pub type PendingQueuesByRange = Vec<(String, PendingQueuesSummary)>;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct PendingQueues {
+    pub by_range: PendingQueuesByRange,
+    /// Count of pending deposits of new validators not in any pubkey group
+    pub unattributed_deposits: usize,
+    /// Sum of `unattributed_deposits` amounts in Gwei
+    pub unattributed_deposits_amount: u64,
+}
+
+/// Attribute the Electra pending deposits, partial withdrawals and consolidations to each group.
+/// Deposits of new validators are attributed to the group of the same name in `pubkey_groups`.
+pub fn group_pending_queues(
+    index_groups: &IndexGroups,
+    pubkey_groups: &PubkeyGroups,
+    state: &StatePartial,
+) -> PendingQueues {
+    // Deposits reference validators by pubkey, only resolve the pubkeys in the queue
+    let mut deposit_pubkeys: HashMap<&BlsPubkey, Option<usize>> = state
+        .pending_deposits
+        .iter()
+        .map(|deposit| (&deposit.pubkey, None))
+        .collect();
+    if !deposit_pubkeys.is_empty() {
+        for (index, validator) in state.validators.iter().enumerate() {
+            if let Some(deposit_index) = deposit_pubkeys.get_mut(&validator.pubkey) {
+                deposit_index.get_or_insert(index);
+            }
+        }
+    }
+
+    let new_validator_pubkeys: HashSet<&BlsPubkey> = deposit_pubkeys
+        .iter()
+        .filter(|(_, index)| index.is_none())
+        .map(|(pubkey, _)| *pubkey)
+        .collect();
+    // Only resolve the pubkey groups of the new validators in the queue
+    let mut pubkey_group_names: HashMap<&BlsPubkey, Vec<&str>> = HashMap::new();
+    if !new_validator_pubkeys.is_empty() {
+        for (name, pubkeys) in pubkey_groups.iter() {
+            for pubkey in pubkeys.iter() {
+                if new_validator_pubkeys.contains(pubkey) {
+                    pubkey_group_names.entry(pubkey).or_default().push(name);
+                }
+            }
+        }
+    }
+
+    let mut unattributed_deposits = 0;
+    let mut unattributed_deposits_amount = 0;
+    for deposit in state.pending_deposits.iter() {
+        if new_validator_pubkeys.contains(&deposit.pubkey)
+            && !pubkey_group_names.contains_key(&deposit.pubkey)
+        {
+            unattributed_deposits += 1;
+            unattributed_deposits_amount += deposit.amount;
+        }
+    }
+
+    let by_range = index_groups
+        .iter()
+        .map(|(range_name, indexes)| {
+            // indexes of each group are sorted
+            let in_group = |index: &usize| indexes.binary_search(index).is_ok();
+            let mut summary = PendingQueuesSummary::default();
+
+            for deposit in state.pending_deposits.iter() {
+                let in_group = match deposit_pubkeys.get(&deposit.pubkey).copied().flatten() {
+                    Some(index) => in_group(&index),
+                    None => pubkey_group_names
+                        .get(&deposit.pubkey)
+                        .is_some_and(|names| names.contains(&range_name.as_str())),
+                };
+                if in_group {
+                    summary.deposits += 1;
+                    summary.deposits_amount += deposit.amount;
+                }
+            }
+
+            for withdrawal in state.pending_partial_withdrawals.iter() {
+                if in_group(&withdrawal.validator_index) {
+                    summary.partial_withdrawals += 1;
+                    summary.partial_withdrawals_amount += withdrawal.amount;
+                }
+            }
+
+            let mut consolidating_indexes = Vec::new();
+            for consolidation in state.pending_consolidations.iter() {
+                if in_group(&consolidation.source_index) {
+                    summary.consolidations_source += 1;
+                    consolidating_indexes.push(consolidation.source_index);
+                }
+                if in_group(&consolidation.target_index) {
+                    summary.consolidations_target += 1;
+                    consolidating_indexes.push(consolidation.target_index);
+                }
+            }
+            consolidating_indexes.sort_unstable();
+            consolidating_indexes.dedup();
+            summary.consolidating_validators = consolidating_indexes.len();
+
+            (range_name.clone(), summary)
+        })
+        .collect();
+
+    PendingQueues {
+        by_range,
+        unattributed_deposits,
+        unattributed_deposits_amount,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ForkName;
+    use crate::ssz_state::{
+        Checkpoint, PendingConsolidation, PendingDeposit, PendingPartialWithdrawal, Validator,
+        FAR_FUTURE_EPOCH,
+    };
+
+    fn checkpoint() -> Checkpoint {
+        Checkpoint {
+            epoch: 0,
+            root: [0; 32],
+        }
+    }
+
+    #[test]
+    fn group_pending_queues_test() {
+        let validators: Vec<Validator> = (0..10)
+            .map(|i| Validator {
+                pubkey: [i as u8; 48],
+                withdrawal_credentials: [0; 32],
+                effective_balance: 32_000_000_000,
+                slashed: false,
+                activation_eligibility_epoch: 0,
+                activation_epoch: 0,
+                exit_epoch: FAR_FUTURE_EPOCH,
+                withdrawable_epoch: FAR_FUTURE_EPOCH,
+            })
+            .collect();
+        let deposit = |pubkey: u8, amount: u64| PendingDeposit {
+            pubkey: [pubkey; 48],
+            amount,
+            slot: 0,
+        };
+        let state = StatePartial {
+            fork: ForkName::Electra,
+            genesis_time: 0,
+            slot: 0,
+            participation_epoch: 0,
+            balances: vec![32_000_000_000; validators.len()],
+            previous_epoch_participation: vec![0; validators.len()],
+            current_epoch_participation: vec![0; validators.len()],
+            inactivity_scores: vec![0; validators.len()],
+            validators,
+            justification_bits: 0,
+            previous_justified_checkpoint: checkpoint(),
+            current_justified_checkpoint: checkpoint(),
+            finalized_checkpoint: checkpoint(),
+            current_sync_committee: vec![],
+            next_sync_committee: vec![],
+            // Top-ups to 1 and 6, plus deposits for new validators in and out of pubkey groups
+            pending_deposits: vec![
+                deposit(1, 1_000),
+                deposit(6, 2_000),
+                deposit(99, 32_000),
+                deposit(98, 64_000),
+            ],
+            pending_partial_withdrawals: vec![
+                PendingPartialWithdrawal {
+                    validator_index: 2,
+                    amount: 500,
+                    withdrawable_epoch: 10,
+                },
+                PendingPartialWithdrawal {
+                    validator_index: 3,
+                    amount: 700,
+                    withdrawable_epoch: 10,
+                },
+            ],
+            pending_consolidations: vec![
+                PendingConsolidation {
+                    source_index: 4,
+                    target_index: 5,
+                },
+                PendingConsolidation {
+                    source_index: 7,
+                    target_index: 5,
+                },
+            ],
+        };
+        let index_groups: IndexGroups = vec![
+            ("a".to_owned(), (0..5).collect()),
+            ("b".to_owned(), (5..10).collect()),
+        ];
+
+        let pubkey_groups: PubkeyGroups = vec![("b".to_owned(), vec![[6; 48], [98; 48]])];
+
+        let pending_queues = group_pending_queues(&index_groups, &pubkey_groups, &state);
+        assert_eq!(pending_queues.unattributed_deposits, 1);
+        assert_eq!(pending_queues.unattributed_deposits_amount, 32_000);
+        assert_eq!(
+            pending_queues.by_range,
+            vec![
+                (
+                    "a".to_owned(),
+                    PendingQueuesSummary {
+                        deposits: 1,
+                        deposits_amount: 1_000,
+                        partial_withdrawals: 2,
+                        partial_withdrawals_amount: 1_200,
+                        consolidations_source: 1,
+                        consolidations_target: 0,
+                        consolidating_validators: 1,
+                    }
+                ),
+                (
+                    "b".to_owned(),
+                    PendingQueuesSummary {
+                        deposits: 2,
+                        deposits_amount: 66_000,
+                        partial_withdrawals: 0,
+                        partial_withdrawals_amount: 0,
+                        consolidations_source: 1,
+                        consolidations_target: 2,
+                        consolidating_validators: 2,
+                    }
+                ),
+            ]
+        );
+    }
+}
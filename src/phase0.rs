@@ -0,0 +1,463 @@
+use crate::config::ConfigSpec;
+use crate::ssz_state::{Validator, TIMELY_HEAD, TIMELY_SOURCE, TIMELY_TARGET};
+use anyhow::{anyhow, Result};
+use byteorder::{ByteOrder, LittleEndian};
+use sha2::{Digest, Sha256};
+
+// class PendingAttestation(Container):
+//     aggregation_bits: Bitlist[MAX_VALIDATORS_PER_COMMITTEE] - 4 bytes (offset)
+//     data: AttestationData - 128 bytes
+//     inclusion_delay: Slot - 8 bytes
+//     proposer_index: ValidatorIndex - 8 bytes
+//
+// class AttestationData(Container):
+//     slot: Slot - 8 bytes
+//     index: CommitteeIndex - 8 bytes
+//     beacon_block_root: Root - 32 bytes
+//     source: Checkpoint - 8+32 = 40 bytes
+//     target: Checkpoint - 8+32 = 40 bytes
+const PENDING_ATTESTATION_FIXED_SIZE: usize = 4 + 128 + 8 + 8;
+
+// https://github.com/ethereum/consensus-specs/blob/dev/specs/phase0/beacon-chain.md#domain-types
+const DOMAIN_BEACON_ATTESTER: [u8; 4] = [1, 0, 0, 0];
+
+#[derive(Debug, PartialEq)]
+struct PendingAttestation {
+    aggregation_bits: Vec<bool>,
+    slot: u64,
+    index: u64,
+    beacon_block_root: [u8; 32],
+    target_epoch: u64,
+    target_root: [u8; 32],
+}
+
+/// Fields of a phase0 BeaconState needed to reconstruct participation from pending attestations
+pub struct Phase0State<'a> {
+    pub config: &'a ConfigSpec,
+    pub slot: u64,
+    pub validators: &'a [Validator],
+    /// Serialized `block_roots` vector
+    pub block_roots: &'a [u8],
+    /// Serialized `randao_mixes` vector
+    pub randao_mixes: &'a [u8],
+}
+
+impl Phase0State<'_> {
+    /// Reconstruct Altair-like participation flags of `epoch` from its pending attestations.
+    /// Phase0 rewards matching source, target and head regardless of inclusion delay, so the
+    /// flags reflect matching attestations.
+    pub fn epoch_participation(&self, attestations: &[u8], epoch: u64) -> Result<Vec<u8>> {
+        let mut participation = vec![0u8; self.validators.len()];
+        let attestations = deserialize_pending_attestations(attestations)?;
+        if attestations.is_empty() {
+            return Ok(participation);
+        }
+
+        let committees = EpochCommittees::compute(self, epoch);
+        // Not known if the epoch has just started, no attestation can match it yet
+        let target_root = self.block_root_at_slot(epoch * self.config.slots_per_epoch);
+
+        for attestation in attestations {
+            if attestation.target_epoch != epoch {
+                return Err(anyhow!(
+                    "pending attestation target epoch {} != {}",
+                    attestation.target_epoch,
+                    epoch
+                ));
+            }
+            let committee = committees
+                .committee(attestation.slot, attestation.index)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "no committee for slot {} index {}",
+                        attestation.slot,
+                        attestation.index
+                    )
+                })?;
+            if committee.len() != attestation.aggregation_bits.len() {
+                return Err(anyhow!(
+                    "aggregation_bits len {} != committee len {} at slot {} index {}",
+                    attestation.aggregation_bits.len(),
+                    committee.len(),
+                    attestation.slot,
+                    attestation.index
+                ));
+            }
+
+            // All pending attestations have a matching source, checked on inclusion
+            let mut flags = TIMELY_SOURCE;
+            if target_root == Some(attestation.target_root) {
+                flags |= TIMELY_TARGET;
+                if self.block_root_at_slot(attestation.slot) == Some(attestation.beacon_block_root)
+                {
+                    flags |= TIMELY_HEAD;
+                }
+            }
+
+            for (index, _) in committee
+                .iter()
+                .zip(attestation.aggregation_bits.iter())
+                .filter(|(_, bit)| **bit)
+            {
+                participation[*index] |= flags;
+            }
+        }
+
+        Ok(participation)
+    }
+
+    /// Spec `get_block_root_at_slot`, None if `slot` is not in the state history
+    fn block_root_at_slot(&self, slot: u64) -> Option<[u8; 32]> {
+        let slots_per_historical_root = self.config.slots_per_historical_root as u64;
+        if slot >= self.slot || self.slot > slot + slots_per_historical_root {
+            return None;
+        }
+        let i = (slot % slots_per_historical_root) as usize * 32;
+        self.block_roots.get(i..i + 32)?.try_into().ok()
+    }
+
+    /// Spec `get_seed` with domain `DOMAIN_BEACON_ATTESTER`
+    fn attester_seed(&self, epoch: u64) -> [u8; 32] {
+        let epochs_per_historical_vector = self.config.epochs_per_historical_vector as u64;
+        let mix_epoch = (epoch + epochs_per_historical_vector - self.config.min_seed_lookahead - 1)
+            % epochs_per_historical_vector;
+        let i = mix_epoch as usize * 32;
+        hash(&[
+            &DOMAIN_BEACON_ATTESTER,
+            &epoch.to_le_bytes(),
+            &self.randao_mixes[i..i + 32],
+        ])
+    }
+}
+
+/// Beacon committees of an epoch
+struct EpochCommittees {
+    /// Active validator indexes in shuffled order, committees are contiguous slices of it
+    shuffled_indexes: Vec<usize>,
+    committees_per_slot: u64,
+    slots_per_epoch: u64,
+}
+
+impl EpochCommittees {
+    fn compute(state: &Phase0State, epoch: u64) -> Self {
+        let config = state.config;
+        let active_indexes: Vec<usize> = state
+            .validators
+            .iter()
+            .enumerate()
+            .filter(|(_, validator)| validator.is_active(epoch))
+            .map(|(index, _)| index)
+            .collect();
+
+        // Spec `get_committee_count_per_slot`
+        let committees_per_slot =
+            (active_indexes.len() as u64 / config.slots_per_epoch / config.target_committee_size)
+                .clamp(1, config.max_committees_per_slot);
+
+        let seed = state.attester_seed(epoch);
+        let shuffled_indexes =
+            shuffle_indexes(active_indexes.len(), &seed, config.shuffle_round_count)
+                .into_iter()
+                .map(|i| active_indexes[i])
+                .collect();
+
+        EpochCommittees {
+            shuffled_indexes,
+            committees_per_slot,
+            slots_per_epoch: config.slots_per_epoch,
+        }
+    }
+
+    /// Spec `get_beacon_committee`
+    fn committee(&self, slot: u64, index: u64) -> Option<&[usize]> {
+        if index >= self.committees_per_slot {
+            return None;
+        }
+        let count = self.committees_per_slot * self.slots_per_epoch;
+        let i = (slot % self.slots_per_epoch) * self.committees_per_slot + index;
+        let len = self.shuffled_indexes.len() as u64;
+        let start = (len * i / count) as usize;
+        let end = (len * (i + 1) / count) as usize;
+        Some(&self.shuffled_indexes[start..end])
+    }
+}
+
+fn hash(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+/// Returns `compute_shuffled_index(i, index_count, seed)` for each i in 0..index_count. Same as
+/// the spec function, but sharing the pivot and source hashes of each round across indexes.
+fn shuffle_indexes(index_count: usize, seed: &[u8; 32], shuffle_round_count: u8) -> Vec<usize> {
+    if index_count == 0 {
+        return vec![];
+    }
+    let count = index_count as u64;
+    let mut indexes: Vec<u64> = (0..count).collect();
+
+    for round in 0..shuffle_round_count {
+        let pivot = LittleEndian::read_u64(&hash(&[seed, &[round]])[0..8]) % count;
+        let sources: Vec<[u8; 32]> = (0..=(count - 1) / 256)
+            .map(|chunk| hash(&[seed, &[round], &(chunk as u32).to_le_bytes()]))
+            .collect();
+
+        for index in indexes.iter_mut() {
+            let flip = (pivot + count - *index) % count;
+            let position = (*index).max(flip);
+            let byte = sources[(position / 256) as usize][((position % 256) / 8) as usize];
+            if (byte >> (position % 8)) & 1 == 1 {
+                *index = flip;
+            }
+        }
+    }
+
+    indexes.into_iter().map(|index| index as usize).collect()
+}
+
+/// Deserialize a `List[PendingAttestation, ...]`. Items are variable size, so the list starts
+/// with one offset per item.
+fn deserialize_pending_attestations(buf: &[u8]) -> Result<Vec<PendingAttestation>> {
+    if buf.is_empty() {
+        return Ok(vec![]);
+    }
+    let first_offset = read_offset(buf, 0)?;
+    if first_offset % 4 != 0 || first_offset > buf.len() {
+        return Err(anyhow!(
+            "invalid first pending attestation offset {}",
+            first_offset
+        ));
+    }
+    let offsets = (0..first_offset / 4)
+        .map(|i| read_offset(buf, i * 4))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut attestations = Vec::with_capacity(offsets.len());
+    for (i, start) in offsets.iter().enumerate() {
+        let end = offsets.get(i + 1).copied().unwrap_or(buf.len());
+        if end < *start || end > buf.len() || end - start < PENDING_ATTESTATION_FIXED_SIZE {
+            return Err(anyhow!(
+                "invalid pending attestation {} offsets {}..{}",
+                i,
+                start,
+                end
+            ));
+        }
+        let item = &buf[*start..end];
+        let aggregation_bits_offset = read_offset(item, 0)?;
+        if aggregation_bits_offset != PENDING_ATTESTATION_FIXED_SIZE {
+            return Err(anyhow!(
+                "invalid aggregation_bits offset {}",
+                aggregation_bits_offset
+            ));
+        }
+        attestations.push(PendingAttestation {
+            aggregation_bits: parse_bitlist(&item[aggregation_bits_offset..])?,
+            slot: LittleEndian::read_u64(&item[4..12]),
+            index: LittleEndian::read_u64(&item[12..20]),
+            beacon_block_root: item[20..52].try_into().expect("slice of 32 bytes"),
+            target_epoch: LittleEndian::read_u64(&item[92..100]),
+            target_root: item[100..132].try_into().expect("slice of 32 bytes"),
+        });
+    }
+    Ok(attestations)
+}
+
+/// Parse an SSZ Bitlist, where the highest set bit of the last byte marks the length
+fn parse_bitlist(bytes: &[u8]) -> Result<Vec<bool>> {
+    let last_byte = *bytes
+        .last()
+        .ok_or_else(|| anyhow!("empty bitlist, missing length bit"))?;
+    if last_byte == 0 {
+        return Err(anyhow!("bitlist last byte is zero, missing length bit"));
+    }
+    let len = (bytes.len() - 1) * 8 + (7 - last_byte.leading_zeros() as usize);
+    Ok((0..len)
+        .map(|i| (bytes[i / 8] >> (i % 8)) & 1 == 1)
+        .collect())
+}
+
+fn read_offset(buf: &[u8], position: usize) -> Result<usize> {
+    let bytes = buf
+        .get(position..position + 4)
+        .ok_or_else(|| anyhow!("offset at {} out of bounds", position))?;
+    Ok(LittleEndian::read_u32(bytes) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ForkName;
+    use crate::ssz_state::FAR_FUTURE_EPOCH;
+
+    const CONFIG_MINIMAL: ConfigSpec = ConfigSpec {
+        seconds_per_slot: 6,
+        slots_per_epoch: 8,
+        slots_per_historical_root: 64,
+        epochs_per_historical_vector: 64,
+        epochs_per_slashings_vector: 64,
+        sync_committee_size: 32,
+        epochs_per_sync_committee_period: 8,
+        max_committees_per_slot: 4,
+        target_committee_size: 4,
+        shuffle_round_count: 10,
+        min_seed_lookahead: 1,
+        fork_versions: [None; ForkName::ALL.len()],
+    };
+
+    /// Spec `compute_shuffled_index`, as written in the spec
+    fn compute_shuffled_index(
+        mut index: u64,
+        index_count: u64,
+        seed: &[u8; 32],
+        rounds: u8,
+    ) -> u64 {
+        for round in 0..rounds {
+            let pivot = LittleEndian::read_u64(&hash(&[seed, &[round]])[0..8]) % index_count;
+            let flip = (pivot + index_count - index) % index_count;
+            let position = index.max(flip);
+            let source = hash(&[seed, &[round], &((position / 256) as u32).to_le_bytes()]);
+            let byte = source[((position % 256) / 8) as usize];
+            if (byte >> (position % 8)) % 2 == 1 {
+                index = flip;
+            }
+        }
+        index
+    }
+
+    #[test]
+    fn shuffle_indexes_test() {
+        let seed = hash(&[b"seed"]);
+        for index_count in [1, 2, 7, 255, 256, 257, 1000] {
+            let shuffled = shuffle_indexes(index_count, &seed, 90);
+            for (i, shuffled_index) in shuffled.iter().enumerate() {
+                assert_eq!(
+                    *shuffled_index as u64,
+                    compute_shuffled_index(i as u64, index_count as u64, &seed, 90),
+                    "index {} of {}",
+                    i,
+                    index_count
+                );
+            }
+            let mut sorted = shuffled.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, (0..index_count).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn parse_bitlist_test() {
+        assert_eq!(
+            parse_bitlist(&[0b0001_1011]).unwrap(),
+            vec![true, true, false, true]
+        );
+        assert_eq!(parse_bitlist(&[0xff, 0b01]).unwrap(), vec![true; 8]);
+        assert!(parse_bitlist(&[0b1, 0]).is_err());
+        assert!(parse_bitlist(&[]).is_err());
+    }
+
+    fn pending_attestation_bytes(
+        aggregation_bits: &[u8],
+        slot: u64,
+        index: u64,
+        beacon_block_root: [u8; 32],
+        target_epoch: u64,
+        target_root: [u8; 32],
+    ) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend((PENDING_ATTESTATION_FIXED_SIZE as u32).to_le_bytes());
+        buf.extend(slot.to_le_bytes());
+        buf.extend(index.to_le_bytes());
+        buf.extend(beacon_block_root);
+        buf.extend([0; 40]); // source
+        buf.extend(target_epoch.to_le_bytes());
+        buf.extend(target_root);
+        buf.extend(1u64.to_le_bytes()); // inclusion_delay
+        buf.extend(0u64.to_le_bytes()); // proposer_index
+        buf.extend(aggregation_bits);
+        buf
+    }
+
+    fn list_bytes(items: &[Vec<u8>]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut offset = 4 * items.len();
+        for item in items {
+            buf.extend((offset as u32).to_le_bytes());
+            offset += item.len();
+        }
+        for item in items {
+            buf.extend(item);
+        }
+        buf
+    }
+
+    #[test]
+    fn epoch_participation_test() {
+        let validators: Vec<Validator> = (0..64)
+            .map(|i| Validator {
+                pubkey: [i as u8; 48],
+                withdrawal_credentials: [0; 32],
+                effective_balance: 32_000_000_000,
+                slashed: false,
+                activation_eligibility_epoch: 0,
+                activation_epoch: 0,
+                exit_epoch: FAR_FUTURE_EPOCH,
+                withdrawable_epoch: FAR_FUTURE_EPOCH,
+            })
+            .collect();
+        let block_roots: Vec<u8> = (0..64u8).flat_map(|slot| [slot; 32]).collect();
+        let randao_mixes: Vec<u8> = (0..64u8).flat_map(|epoch| [epoch + 100; 32]).collect();
+        let state = Phase0State {
+            config: &CONFIG_MINIMAL,
+            slot: 25,
+            validators: &validators,
+            block_roots: &block_roots,
+            randao_mixes: &randao_mixes,
+        };
+
+        // 64 active validators, 2 committees per slot of 4 validators each
+        let committees = EpochCommittees::compute(&state, 2);
+        assert_eq!(committees.committees_per_slot, 2);
+        let committee_a = committees.committee(17, 0).unwrap().to_vec();
+        let committee_b = committees.committee(18, 1).unwrap().to_vec();
+        assert_eq!(committee_a.len(), 4);
+        assert!(committees.committee(18, 2).is_none());
+
+        let attestations = list_bytes(&[
+            // Matching head and target, bits 0, 1 and 3
+            pending_attestation_bytes(&[0b0001_1011], 17, 0, [17; 32], 2, [16; 32]),
+            // Matching target only, all bits
+            pending_attestation_bytes(&[0b0001_1111], 18, 1, [0xff; 32], 2, [16; 32]),
+        ]);
+        let participation = state.epoch_participation(&attestations, 2).unwrap();
+
+        let all_flags = TIMELY_SOURCE | TIMELY_TARGET | TIMELY_HEAD;
+        assert_eq!(participation[committee_a[0]], all_flags);
+        assert_eq!(participation[committee_a[1]], all_flags);
+        assert_eq!(participation[committee_a[2]], 0);
+        assert_eq!(participation[committee_a[3]], all_flags);
+        for index in committee_b.iter() {
+            assert_eq!(participation[*index], TIMELY_SOURCE | TIMELY_TARGET);
+        }
+        assert_eq!(
+            participation.iter().filter(|flags| **flags != 0).count(),
+            3 + committee_b.len()
+        );
+
+        // Committee size mismatch
+        let attestations = list_bytes(&[pending_attestation_bytes(
+            &[0b0000_0111],
+            17,
+            0,
+            [17; 32],
+            2,
+            [16; 32],
+        )]);
+        assert!(state.epoch_participation(&attestations, 2).is_err());
+
+        assert_eq!(state.epoch_participation(&[], 2).unwrap(), vec![0; 64]);
+    }
+}
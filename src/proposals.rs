@@ -0,0 +1,231 @@
+use crate::ranges::IndexGroups;
+use anyhow::{anyhow, Result};
+use log::debug;
+use reqwest::{header::HeaderMap, StatusCode};
+use serde::Deserialize;
+
+/// Validator scheduled to propose a block at `slot`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProposerDuty {
+    pub slot: u64,
+    pub validator_index: usize,
+}
+
+/// Proposer duties of a full epoch, as returned by the beacon node during that epoch
+#[derive(Debug)]
+pub struct EpochProposerDuties {
+    pub epoch: u64,
+    pub duties: Vec<ProposerDuty>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Proposal {
+    pub duty: ProposerDuty,
+    pub proposed: bool,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct ProposalSummary {
+    pub scheduled: u64,
+    pub proposed: u64,
+    pub missed: u64,
+}
+
+pub type ProposalsByRange = Vec<(String, ProposalSummary)>;
+
+#[derive(Deserialize)]
+struct ProposerDutiesResponse {
+    data: Vec<ProposerDutyData>,
+}
+
+#[derive(Deserialize)]
+struct ProposerDutyData {
+    validator_index: String,
+    slot: String,
+}
+
+#[derive(Deserialize)]
+struct BlockHeaderResponse {
+    data: BlockHeaderData,
+}
+
+#[derive(Deserialize)]
+struct BlockHeaderData {
+    canonical: bool,
+    header: SignedBlockHeader,
+}
+
+#[derive(Deserialize)]
+struct SignedBlockHeader {
+    message: BlockHeader,
+}
+
+#[derive(Deserialize)]
+struct BlockHeader {
+    slot: String,
+    proposer_index: String,
+}
+
+/// Fetch the proposer duties of `epoch`. Beacon nodes may only serve duties for the current
+/// epoch, so duties should be fetched during the epoch and checked once it has ended.
+pub async fn fetch_proposer_duties(
+    beacon_url: &str,
+    extra_headers: &HeaderMap,
+    epoch: u64,
+) -> Result<EpochProposerDuties> {
+    let res = reqwest::Client::new()
+        .get(format!(
+            "{beacon_url}/eth/v1/validator/duties/proposer/{epoch}"
+        ))
+        .headers(extra_headers.clone())
+        .send()
+        .await?;
+    if !res.status().is_success() {
+        return Err(anyhow!(
+            "getProposerDuties returned not success code {}",
+            res.status().as_str()
+        ));
+    }
+
+    let data: ProposerDutiesResponse = res.json().await?;
+    let mut duties = Vec::with_capacity(data.data.len());
+    for duty in data.data {
+        duties.push(ProposerDuty {
+            slot: duty.slot.parse()?,
+            validator_index: duty.validator_index.parse()?,
+        });
+    }
+    Ok(EpochProposerDuties { epoch, duties })
+}
+
+/// Fetch the proposer index of the canonical block at `slot`. Returns None if the slot has no
+/// canonical block
+async fn fetch_block_proposer(
+    beacon_url: &str,
+    extra_headers: &HeaderMap,
+    slot: u64,
+) -> Result<Option<usize>> {
+    let res = reqwest::Client::new()
+        .get(format!("{beacon_url}/eth/v1/beacon/headers/{slot}"))
+        .headers(extra_headers.clone())
+        .send()
+        .await?;
+
+    // Missed slots have no block
+    if res.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !res.status().is_success() {
+        return Err(anyhow!(
+            "getBlockHeader returned not success code {}",
+            res.status().as_str()
+        ));
+    }
+
+    let data: BlockHeaderResponse = res.json().await?;
+    let header = data.data.header.message;
+    // Some nodes return the header of the last block before a missed slot
+    if !data.data.canonical || header.slot.parse::<u64>()? != slot {
+        return Ok(None);
+    }
+    Ok(Some(header.proposer_index.parse()?))
+}
+
+/// Check each duty of a past epoch against the block header at its slot
+pub async fn fetch_proposals(
+    beacon_url: &str,
+    extra_headers: &HeaderMap,
+    duties: &EpochProposerDuties,
+) -> Result<Vec<Proposal>> {
+    let mut proposals = Vec::with_capacity(duties.duties.len());
+    for duty in duties.duties.iter() {
+        let proposer_index = fetch_block_proposer(beacon_url, extra_headers, duty.slot).await?;
+        let proposed = proposer_index == Some(duty.validator_index);
+        if !proposed {
+            debug!(
+                "missed proposal at slot {} by validator {}",
+                duty.slot, duty.validator_index
+            );
+        }
+        proposals.push(Proposal {
+            duty: duty.clone(),
+            proposed,
+        });
+    }
+    Ok(proposals)
+}
+
+pub fn group_proposals(index_groups: &IndexGroups, proposals: &[Proposal]) -> ProposalsByRange {
+    index_groups
+        .iter()
+        .map(|(range_name, indexes)| {
+            let mut summary = ProposalSummary::default();
+            for proposal in proposals {
+                // indexes of each group are sorted
+                if indexes
+                    .binary_search(&proposal.duty.validator_index)
+                    .is_ok()
+                {
+                    summary.scheduled += 1;
+                    if proposal.proposed {
+                        summary.proposed += 1;
+                    } else {
+                        summary.missed += 1;
+                    }
+                }
+            }
+            (range_name.clone(), summary)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proposal(slot: u64, validator_index: usize, proposed: bool) -> Proposal {
+        Proposal {
+            duty: ProposerDuty {
+                slot,
+                validator_index,
+            },
+            proposed,
+        }
+    }
+
+    #[test]
+    fn group_proposals_test() {
+        let index_groups: IndexGroups = vec![
+            ("a".to_owned(), (0..10).collect()),
+            ("b".to_owned(), (5..20).collect()),
+        ];
+        let proposals = [
+            proposal(32, 1, true),
+            proposal(33, 7, false),
+            proposal(34, 15, true),
+            proposal(35, 100, false),
+        ];
+
+        assert_eq!(
+            group_proposals(&index_groups, &proposals),
+            vec![
+                (
+                    "a".to_owned(),
+                    ProposalSummary {
+                        scheduled: 2,
+                        proposed: 1,
+                        missed: 1
+                    }
+                ),
+                (
+                    "b".to_owned(),
+                    ProposalSummary {
+                        scheduled: 2,
+                        proposed: 1,
+                        missed: 1
+                    }
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use crate::ranges::IndexGroups;
+use crate::ssz_state::{BlsPubkey, Validator};
+use crate::util::resolve_path_or_url;
+use crate::withdrawals::WithdrawalGrouping;
+use anyhow::{anyhow, Context, Result};
+use std::collections::{HashMap, HashSet};
+
+/// Named groups of validator pubkeys, in file order. Pubkeys of each group are unique
+pub type PubkeyGroups = Vec<(String, Vec<BlsPubkey>)>;
+
+/// Groups of index ranges, of pubkeys and of withdrawal addresses. Pubkeys and withdrawal
+/// addresses are resolved to indexes with the registry of each state, since the indexes of new
+/// validators are not known until their deposit is processed
+#[derive(Debug, Default, PartialEq)]
+pub struct Groups {
+    pub ranges: IndexGroups,
+    pub pubkeys: PubkeyGroups,
+    pub withdrawals: WithdrawalGrouping,
+}
+
+impl Groups {
+    /// Index groups of the ranges, the pubkeys found in `validators` and their withdrawal
+    /// addresses, and the count of pubkeys not found per pubkey group. Groups with the same name
+    /// are merged.
+    pub fn resolve(&self, validators: &[Validator]) -> (IndexGroups, Vec<(String, usize)>) {
+        let mut index_groups = self.ranges.clone();
+        for (name, indexes) in self.withdrawals.group(validators) {
+            merge_group(&mut index_groups, name, indexes);
+        }
+        let mut unresolved = Vec::with_capacity(self.pubkeys.len());
+        if self.pubkeys.is_empty() {
+            return (index_groups, unresolved);
+        }
+
+        let index_by_pubkey: HashMap<&BlsPubkey, usize> = validators
+            .iter()
+            .enumerate()
+            .map(|(index, validator)| (&validator.pubkey, index))
+            .collect();
+        for (name, pubkeys) in self.pubkeys.iter() {
+            let indexes: Vec<usize> = pubkeys
+                .iter()
+                .filter_map(|pubkey| index_by_pubkey.get(pubkey).copied())
+                .collect();
+            unresolved.push((name.clone(), pubkeys.len() - indexes.len()));
+            merge_group(&mut index_groups, name.clone(), indexes);
+        }
+        (index_groups, unresolved)
+    }
+}
+
+/// Add `indexes` to the group `name`, creating it if new
+pub fn merge_group(index_groups: &mut IndexGroups, name: String, indexes: Vec<usize>) {
+    match index_groups.iter_mut().find(|(n, _)| *n == name) {
+        Some((_, group_indexes)) => {
+            group_indexes.extend(indexes);
+            group_indexes.sort_unstable();
+            group_indexes.dedup();
+        }
+        None => {
+            let mut indexes = indexes;
+            indexes.sort_unstable();
+            indexes.dedup();
+            index_groups.push((name, indexes));
+        }
+    }
+}
+
+/// Load a pubkeys groups file as YAML or JSON mapping each group name to a list of pubkeys, or
+/// to a local path or URL of a file with one pubkey per line
+/// ```yaml
+/// entityA lighthouse-geth-0:
+///   - 0x8f2a...
+///   - 0xa1b3...
+/// entityB lodestar-nethermind-0: https://example.com/entityB-pubkeys.txt
+/// ```
+/// Otherwise as TXT with a pubkey and its group name per line: `0x8f2a... entityA lighthouse-geth-0`
+pub async fn load_pubkey_groups(input: &str) -> Result<PubkeyGroups> {
+    let mapping = match serde_yaml::from_str::<serde_yaml::Mapping>(input) {
+        Ok(mapping) => mapping,
+        Err(_) => return parse_pubkey_groups_as_txt(input),
+    };
+
+    let mut groups = Vec::new();
+    let mut seen = HashMap::new();
+    for (name, value) in mapping {
+        let name = name
+            .as_str()
+            .ok_or_else(|| anyhow!("Invalid group name: {:?}", name))?
+            .to_owned();
+        let pubkeys = match value {
+            serde_yaml::Value::Sequence(pubkeys) => pubkeys
+                .iter()
+                .map(|pubkey| {
+                    pubkey
+                        .as_str()
+                        .ok_or_else(|| anyhow!("Invalid pubkey: {:?}", pubkey))
+                        .and_then(parse_pubkey)
+                })
+                .collect::<Result<Vec<_>>>()
+                .with_context(|| format!("group {}", name))?,
+            serde_yaml::Value::String(path_or_url) => {
+                let pubkeys_str = resolve_path_or_url(&path_or_url)
+                    .await
+                    .with_context(|| format!("loading pubkeys of group {}", name))?;
+                parse_pubkeys_list(&pubkeys_str).with_context(|| format!("group {}", name))?
+            }
+            value => return Err(anyhow!("Invalid pubkeys of group {}: {:?}", name, value)),
+        };
+        push_pubkeys(&mut groups, &mut seen, name, pubkeys);
+    }
+    Ok(groups)
+}
+
+fn parse_pubkey_groups_as_txt(input: &str) -> Result<PubkeyGroups> {
+    let mut groups = Vec::new();
+    let mut seen = HashMap::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if let Some((pubkey_str, name)) = line.split_once(char::is_whitespace) {
+            let pubkey = parse_pubkey(pubkey_str).with_context(|| format!("line {}", i + 1))?;
+            push_pubkeys(&mut groups, &mut seen, name.trim().to_owned(), vec![pubkey]);
+        }
+    }
+    Ok(groups)
+}
+
+/// Parse a file with one pubkey per line, skipping empty lines and `#` comments
+fn parse_pubkeys_list(input: &str) -> Result<Vec<BlsPubkey>> {
+    input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(parse_pubkey)
+        .collect()
+}
+
+fn parse_pubkey(pubkey_str: &str) -> Result<BlsPubkey> {
+    let bytes = hex::decode(pubkey_str.trim().trim_start_matches("0x"))
+        .with_context(|| format!("Invalid pubkey hex: {}", pubkey_str))?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow!("Pubkey not 48 bytes: {}", pubkey_str))
+}
+
+/// Append `pubkeys` to the group `name`, creating it if new. `seen` holds the pubkeys already in
+/// each group, to skip duplicates without scanning the group
+fn push_pubkeys(
+    groups: &mut PubkeyGroups,
+    seen: &mut HashMap<String, HashSet<BlsPubkey>>,
+    name: String,
+    pubkeys: Vec<BlsPubkey>,
+) {
+    let group_seen = seen.entry(name.clone()).or_default();
+    let group_pubkeys = match groups.iter_mut().find(|(n, _)| *n == name) {
+        Some((_, group_pubkeys)) => group_pubkeys,
+        None => {
+            groups.push((name, vec![]));
+            &mut groups.last_mut().expect("just pushed").1
+        }
+    };
+    for pubkey in pubkeys {
+        if group_seen.insert(pubkey) {
+            group_pubkeys.push(pubkey);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssz_state::FAR_FUTURE_EPOCH;
+
+    fn pubkey_hex(byte: u8) -> String {
+        format!("0x{}", hex::encode([byte; 48]))
+    }
+
+    fn validator(byte: u8) -> Validator {
+        Validator {
+            pubkey: [byte; 48],
+            withdrawal_credentials: [0; 32],
+            effective_balance: 32_000_000_000,
+            slashed: false,
+            activation_eligibility_epoch: 0,
+            activation_epoch: 0,
+            exit_epoch: FAR_FUTURE_EPOCH,
+            withdrawable_epoch: FAR_FUTURE_EPOCH,
+        }
+    }
+
+    #[tokio::test]
+    async fn load_pubkey_groups_test() {
+        let yaml = format!(
+            "a:\n  - {}\n  - {}\n  - {}\nb:\n  - {}\n",
+            pubkey_hex(1),
+            pubkey_hex(2),
+            pubkey_hex(1),
+            pubkey_hex(3)
+        );
+        assert_eq!(
+            load_pubkey_groups(&yaml).await.unwrap(),
+            vec![
+                ("a".to_owned(), vec![[1; 48], [2; 48]]),
+                ("b".to_owned(), vec![[3; 48]]),
+            ]
+        );
+
+        let txt = format!(
+            "{} a\n{} b c\n\n{} a\n",
+            pubkey_hex(1),
+            pubkey_hex(3),
+            pubkey_hex(1)
+        );
+        assert_eq!(
+            load_pubkey_groups(&txt).await.unwrap(),
+            vec![
+                ("a".to_owned(), vec![[1; 48]]),
+                ("b c".to_owned(), vec![[3; 48]]),
+            ]
+        );
+
+        assert!(load_pubkey_groups("0x1234 a").await.is_err());
+    }
+
+    #[test]
+    fn resolve_groups_test() {
+        let groups = Groups {
+            ranges: vec![("a".to_owned(), vec![0])],
+            pubkeys: vec![
+                ("a".to_owned(), vec![[2; 48], [9; 48]]),
+                ("b".to_owned(), vec![[1; 48]]),
+            ],
+            ..Default::default()
+        };
+        let validators: Vec<Validator> = (0..3).map(validator).collect();
+        let (index_groups, unresolved) = groups.resolve(&validators);
+        assert_eq!(
+            index_groups,
+            vec![("a".to_owned(), vec![0, 2]), ("b".to_owned(), vec![1])]
+        );
+        assert_eq!(unresolved, vec![("a".to_owned(), 1), ("b".to_owned(), 0)]);
+    }
+}
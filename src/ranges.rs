@@ -1,15 +1,30 @@
 use anyhow::{anyhow, Result};
 use once_cell::sync::Lazy;
 use regex::Regex;
-use std::{collections::HashMap, ops::Range};
+use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    ops::Range,
+};
 
+/// Named groups of validator indexes. Indexes of each group are sorted and unique
 pub type IndexGroups = Vec<(String, Vec<usize>)>;
-type IndexRangesJson = HashMap<String, String>;
 type RangesNotGroup = Vec<(Range<usize>, String)>;
 
-/// Parse group file contents flexibly, either as JSON first or then TXT
+/// Label keys of structured group names, exported as labels of the `beacon_network_range_labels`
+/// info metric to join with the range gauges. Other keys are kept in the group name only.
+pub const LABEL_KEYS: [&str; 4] = ["entity", "cl", "el", "node"];
+
+/// Values of a structured group name in the order of `LABEL_KEYS`, empty if not set
+pub type GroupLabels = [String; LABEL_KEYS.len()];
+
+/// Parse group file contents flexibly, either as JSON first, then YAML or then TXT
 pub fn parse_ranges(input: &str) -> Result<IndexGroups> {
-    let ranges = if let Ok(ranges) = parse_ranges_as_json(input) {
+    let ranges = if let Ok(IndexRangesJson(entries)) = serde_json::from_str(input) {
+        // Valid JSON with invalid ranges or duplicate keys is an error, not a TXT line
+        parse_ranges_as_json(entries)?
+    } else if let Ok(ranges) = parse_ranges_as_yaml(input) {
         ranges
     } else {
         parse_ranges_as_txt(input)?
@@ -52,24 +67,227 @@ fn parse_ranges_as_txt(input: &str) -> Result<RangesNotGroup> {
     Ok(result)
 }
 
-/// Parse JSON file with format
+/// Parse the entries of a JSON file with format
 /// ```json
 /// {
 ///   "0..1000": "entityA lighthouse-geth-0",
 ///   "1000..2000": "entityB lodestar-nethermind-0",
 /// }
 /// ```
-fn parse_ranges_as_json(input: &str) -> Result<RangesNotGroup> {
-    let data: IndexRangesJson = serde_json::from_str(input)?;
+fn parse_ranges_as_json(entries: Vec<(String, String)>) -> Result<RangesNotGroup> {
     let mut result = Vec::new();
-    for (range_str, name) in data {
+    let mut range_strs = HashSet::new();
+    for (range_str, name) in entries {
+        if !range_strs.insert(range_str.clone()) {
+            return Err(anyhow!("Duplicate range key: {}", range_str));
+        }
         result.push((parse_range(&range_str)?, name));
     }
-    // serde_json uses HashMap which does not preserve order. Enforce ascending index order
+    // Enforce ascending index order, same as YAML
     result.sort_by_key(|(range, _)| range.start);
     Ok(result)
 }
 
+/// Entries of a JSON object in file order. Unlike a map, keeps duplicate keys to report them
+struct IndexRangesJson(Vec<(String, String)>);
+
+impl<'de> Deserialize<'de> for IndexRangesJson {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct EntriesVisitor;
+
+        impl<'de> Visitor<'de> for EntriesVisitor {
+            type Value = IndexRangesJson;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an object of index ranges to group names")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<Self::Value, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(IndexRangesJson(entries))
+            }
+        }
+
+        deserializer.deserialize_map(EntriesVisitor)
+    }
+}
+
+/// Parse YAML with either a group name or an object of labels per range
+/// ```yaml
+/// 0..1000: entityA lighthouse-geth-0
+/// 1000..2000:
+///   entity: entityB
+///   cl: lodestar
+///   el: nethermind
+///   node: 0
+/// ```
+fn parse_ranges_as_yaml(input: &str) -> Result<RangesNotGroup> {
+    let data: serde_yaml::Mapping = serde_yaml::from_str(input)?;
+    let mut result = Vec::new();
+    for (key, value) in data {
+        let range_str = yaml_scalar(&key)
+            .filter(|range_str| !range_str.contains(char::is_whitespace))
+            .ok_or_else(|| anyhow!("Invalid range key: {:?}", key))?;
+        let name = match &value {
+            serde_yaml::Value::Mapping(labels) => {
+                let mut label_pairs = Vec::new();
+                for (label_key, label_value) in labels {
+                    let label_key = yaml_scalar(label_key)
+                        .filter(|key| !key.is_empty() && !key.contains(['=', '"']))
+                        .filter(|key| !key.contains(char::is_whitespace))
+                        .ok_or_else(|| anyhow!("Invalid label key: {:?}", label_key))?;
+                    let label_value = yaml_scalar(label_value)
+                        .ok_or_else(|| anyhow!("Invalid {} label: {:?}", label_key, label_value))?;
+                    label_pairs.push((label_key, label_value));
+                }
+                format_group_labels(&label_pairs)?
+            }
+            value => {
+                yaml_scalar(value).ok_or_else(|| anyhow!("Invalid group name: {:?}", value))?
+            }
+        };
+        result.push((parse_range(&range_str)?, name));
+    }
+    result.sort_by_key(|(range, _)| range.start);
+    Ok(result)
+}
+
+fn yaml_scalar(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(s) => Some(s.clone()),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        serde_yaml::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn known_label_key_index(key: &str) -> Option<usize> {
+    LABEL_KEYS.iter().position(|k| *k == key)
+}
+
+fn label_key_index(key: &str) -> Result<usize> {
+    LABEL_KEYS.iter().position(|k| *k == key).ok_or_else(|| {
+        anyhow!(
+            "Unknown label key {:?}, must be one of {}",
+            key,
+            LABEL_KEYS.join(", ")
+        )
+    })
+}
+
+/// Split a structured group name like `entity=Nethermind cl=lighthouse el=geth node=0`, values
+/// with spaces in double quotes, into its key value pairs. Returns None for free-form names like
+/// `Nethermind lighthouse-0`
+fn parse_label_pairs(name: &str) -> Option<Vec<(String, String)>> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut in_quotes = false;
+    for c in name.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            c => token.push(c),
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    if tokens.is_empty() || !tokens.iter().all(|token| token.contains('=')) {
+        return None;
+    }
+    Some(
+        tokens
+            .into_iter()
+            .map(|token| {
+                let (key, value) = token.split_once('=').expect("token contains =");
+                (key.to_owned(), value.to_owned())
+            })
+            .collect(),
+    )
+}
+
+/// Values of the keys of `LABEL_KEYS` of a structured group name. Keys not in `LABEL_KEYS` are
+/// ignored. Returns None for free-form names and names without any of `LABEL_KEYS`
+pub fn parse_group_labels(name: &str) -> Option<GroupLabels> {
+    let mut labels = GroupLabels::default();
+    let mut has_known_key = false;
+    for (key, value) in parse_label_pairs(name)? {
+        if let Some(i) = known_label_key_index(&key) {
+            labels[i] = value;
+            has_known_key = true;
+        }
+    }
+    has_known_key.then_some(labels)
+}
+
+/// Keys of a structured group name not in `LABEL_KEYS`, most likely typos
+pub fn unknown_label_keys(name: &str) -> Vec<String> {
+    parse_label_pairs(name)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(key, _)| key)
+        .filter(|key| known_label_key_index(key).is_none())
+        .collect()
+}
+
+/// Format labels as a structured group name, inverse of `parse_label_pairs`. Keys of
+/// `LABEL_KEYS` come first in their order, so the same labels always give the same name
+fn format_group_labels(label_pairs: &[(String, String)]) -> Result<String> {
+    let mut sorted_pairs: Vec<&(String, String)> = label_pairs.iter().collect();
+    sorted_pairs.sort_by_key(|(key, _)| known_label_key_index(key).unwrap_or(LABEL_KEYS.len()));
+    let mut tokens = Vec::new();
+    for (key, value) in sorted_pairs {
+        if value.contains('"') {
+            return Err(anyhow!("Invalid {} label with quotes: {}", key, value));
+        }
+        if value.contains(char::is_whitespace) {
+            tokens.push(format!("{}=\"{}\"", key, value));
+        } else if !value.is_empty() {
+            tokens.push(format!("{}={}", key, value));
+        }
+    }
+    Ok(tokens.join(" "))
+}
+
+/// Merge the indexes of all groups with structured names by their value of label `key`, to
+/// aggregate across groups. Groups without that label are skipped.
+pub fn group_by_label(index_groups: &IndexGroups, key: &str) -> Result<IndexGroups> {
+    let i = label_key_index(key)?;
+    let mut groups: IndexGroups = Vec::new();
+    for (name, indexes) in index_groups {
+        let Some(labels) = parse_group_labels(name) else {
+            continue;
+        };
+        let value = &labels[i];
+        if value.is_empty() {
+            continue;
+        }
+        match groups
+            .iter_mut()
+            .find(|(group_value, _)| group_value == value)
+        {
+            Some((_, group_indexes)) => group_indexes.extend_from_slice(indexes),
+            None => groups.push((value.clone(), indexes.clone())),
+        }
+    }
+    for (_, indexes) in groups.iter_mut() {
+        indexes.sort_unstable();
+        indexes.dedup();
+    }
+    Ok(groups)
+}
+
 /// Parses a string representing a range with format:
 /// "0-10", "0..10", "[0..10]", "[0-10]", "(0..10)", "[0-10)",
 fn parse_range(input: &str) -> Result<Range<usize>> {
@@ -88,6 +306,340 @@ fn parse_range(input: &str) -> Result<Range<usize>> {
     }
 }
 
+/// Problem found in a ranges file. Lines are 1-based and only known for TXT files.
+#[derive(Debug, PartialEq)]
+pub enum RangeIssue {
+    Unparseable {
+        line: Option<usize>,
+        text: String,
+        reason: String,
+    },
+    Inverted {
+        line: Option<usize>,
+        range: Range<usize>,
+        name: String,
+    },
+    Empty {
+        line: Option<usize>,
+        range: Range<usize>,
+        name: String,
+    },
+    /// Indexes in ranges of two different groups
+    Overlap {
+        first: RangeEntry,
+        second: RangeEntry,
+        indexes: Range<usize>,
+    },
+    /// Indexes between two ranges not in any range
+    Gap { indexes: Range<usize> },
+    /// Indexes of a range not in the registry of `validator_count` validators
+    BeyondRegistry {
+        entry: RangeEntry,
+        validator_count: usize,
+    },
+    /// Keys of a structured group name not in `LABEL_KEYS`, which are not exported as labels
+    UnknownLabelKeys {
+        line: Option<usize>,
+        name: String,
+        keys: Vec<String>,
+    },
+    /// Validators of the registry not in any range
+    Uncovered {
+        count: usize,
+        validator_count: usize,
+    },
+}
+
+impl RangeIssue {
+    /// Errors are most likely typos, others are common in files prepared ahead of deposits or
+    /// covering a subset of the validators
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            RangeIssue::Unparseable { .. }
+                | RangeIssue::Inverted { .. }
+                | RangeIssue::Empty { .. }
+                | RangeIssue::Overlap { .. }
+        )
+    }
+}
+
+fn line_prefix(line: &Option<usize>) -> String {
+    line.map(|line| format!("line {}: ", line))
+        .unwrap_or_default()
+}
+
+impl fmt::Display for RangeIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RangeIssue::Unparseable { line, text, reason } => {
+                write!(
+                    f,
+                    "{}cannot parse {:?}: {}",
+                    line_prefix(line),
+                    text,
+                    reason
+                )
+            }
+            RangeIssue::Inverted { line, range, name } => write!(
+                f,
+                "{}inverted range {}..{} of {}",
+                line_prefix(line),
+                range.start,
+                range.end,
+                name
+            ),
+            RangeIssue::Empty { line, range, name } => write!(
+                f,
+                "{}empty range {}..{} of {}",
+                line_prefix(line),
+                range.start,
+                range.end,
+                name
+            ),
+            RangeIssue::Overlap {
+                first,
+                second,
+                indexes,
+            } => write!(
+                f,
+                "{}range {} of {} overlaps {}range {} of {} at indexes {}..{}",
+                line_prefix(&first.line),
+                fmt_range(&first.range),
+                first.name,
+                line_prefix(&second.line),
+                fmt_range(&second.range),
+                second.name,
+                indexes.start,
+                indexes.end
+            ),
+            RangeIssue::Gap { indexes } => {
+                write!(f, "gap of indexes {} not in any range", fmt_range(indexes))
+            }
+            RangeIssue::BeyondRegistry {
+                entry,
+                validator_count,
+            } => write!(
+                f,
+                "{}range {} of {} has {} indexes beyond the {} validators in the registry",
+                line_prefix(&entry.line),
+                fmt_range(&entry.range),
+                entry.name,
+                entry.range.end - entry.range.start.max(*validator_count),
+                validator_count
+            ),
+            RangeIssue::UnknownLabelKeys { line, name, keys } => write!(
+                f,
+                "{}unknown label keys {} of {}, only {} are exported as labels",
+                line_prefix(line),
+                keys.join(", "),
+                name,
+                LABEL_KEYS.join(", ")
+            ),
+            RangeIssue::Uncovered {
+                count,
+                validator_count,
+            } => write!(
+                f,
+                "{} of the {} validators in the registry are not in any range",
+                count, validator_count
+            ),
+        }
+    }
+}
+
+fn fmt_range(range: &Range<usize>) -> String {
+    format!("{}..{}", range.start, range.end)
+}
+
+/// Range of a ranges file with the line it is defined at, known only for TXT files
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeEntry {
+    pub line: Option<usize>,
+    pub range: Range<usize>,
+    pub name: String,
+}
+
+/// Parse the ranges of a ranges file reporting the lines that do not parse, instead of failing
+/// on the first one or skipping them like `parse_ranges`
+fn parse_range_entries(input: &str) -> (Vec<RangeEntry>, Vec<RangeIssue>) {
+    let entry = |(range, name)| RangeEntry {
+        line: None,
+        range,
+        name,
+    };
+    let json_error = match serde_json::from_str::<IndexRangesJson>(input) {
+        Ok(IndexRangesJson(json_entries)) => {
+            let mut entries = Vec::new();
+            let mut issues = Vec::new();
+            let mut range_strs = HashSet::new();
+            for (range_str, name) in json_entries {
+                let unparseable = |reason: String| RangeIssue::Unparseable {
+                    line: None,
+                    text: range_str.clone(),
+                    reason,
+                };
+                if !range_strs.insert(range_str.clone()) {
+                    issues.push(unparseable("duplicate range key".to_owned()));
+                    continue;
+                }
+                match parse_range(&range_str) {
+                    Err(e) => issues.push(unparseable(e.to_string())),
+                    Ok(range) => entries.push(entry((range, name))),
+                }
+            }
+            return (entries, issues);
+        }
+        Err(e) => e,
+    };
+    if serde_yaml::from_str::<serde_yaml::Mapping>(input).is_ok() {
+        return match parse_ranges_as_yaml(input) {
+            Ok(ranges) => (ranges.into_iter().map(entry).collect(), vec![]),
+            Err(e) => (
+                vec![],
+                vec![RangeIssue::Unparseable {
+                    line: None,
+                    text: "YAML".to_owned(),
+                    reason: e.to_string(),
+                }],
+            ),
+        };
+    }
+    // Do not read invalid JSON as TXT, it would only report each line as unparseable
+    if input.trim_start().starts_with('{') {
+        return (
+            vec![],
+            vec![RangeIssue::Unparseable {
+                line: Some(json_error.line()),
+                text: input
+                    .lines()
+                    .nth(json_error.line().saturating_sub(1))
+                    .unwrap_or_default()
+                    .trim()
+                    .to_owned(),
+                reason: json_error.to_string(),
+            }],
+        );
+    }
+
+    let mut entries = Vec::new();
+    let mut issues = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let text = line.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let unparseable = |reason: String| RangeIssue::Unparseable {
+            line: Some(i + 1),
+            text: text.to_owned(),
+            reason,
+        };
+        match text.split_once(' ') {
+            None => issues.push(unparseable("missing group name after the range".to_owned())),
+            Some((range_str, name)) => match parse_range(range_str) {
+                Err(e) => issues.push(unparseable(e.to_string())),
+                Ok(range) => entries.push(RangeEntry {
+                    line: Some(i + 1),
+                    range,
+                    name: name.trim().to_owned(),
+                }),
+            },
+        }
+    }
+    (entries, issues)
+}
+
+/// Report the problems of a ranges file: unparseable lines, empty or inverted ranges, overlaps
+/// between groups and gaps between ranges. With `validator_count`, also report indexes beyond
+/// the registry, gaps before the first and after the last range and validators not in any range.
+pub fn lint_ranges(input: &str, validator_count: Option<usize>) -> Vec<RangeIssue> {
+    let (entries, mut issues) = parse_range_entries(input);
+
+    let mut entries: Vec<RangeEntry> = entries
+        .into_iter()
+        .filter(|entry| {
+            let (line, range, name) = (entry.line, entry.range.clone(), entry.name.clone());
+            let keys = unknown_label_keys(&name);
+            if !keys.is_empty() {
+                issues.push(RangeIssue::UnknownLabelKeys {
+                    line,
+                    name: name.clone(),
+                    keys,
+                });
+            }
+            if range.start > range.end {
+                issues.push(RangeIssue::Inverted { line, range, name });
+                false
+            } else if range.start == range.end {
+                issues.push(RangeIssue::Empty { line, range, name });
+                false
+            } else {
+                true
+            }
+        })
+        .collect();
+    entries.sort_by_key(|entry| (entry.range.start, entry.range.end));
+
+    for (i, first) in entries.iter().enumerate() {
+        for second in entries[i + 1..]
+            .iter()
+            .take_while(|second| second.range.start < first.range.end)
+        {
+            if first.name != second.name {
+                issues.push(RangeIssue::Overlap {
+                    first: first.clone(),
+                    second: second.clone(),
+                    indexes: second.range.start..first.range.end.min(second.range.end),
+                });
+            }
+        }
+    }
+
+    // Walk the union of all ranges. With the registry size, indexes from 0 are expected
+    let mut covered = 0;
+    let mut covered_end: Option<usize> = validator_count.map(|_| 0);
+    for entry in entries.iter() {
+        let range = &entry.range;
+        match covered_end {
+            Some(end) if range.start > end => {
+                issues.push(RangeIssue::Gap {
+                    indexes: end..range.start,
+                });
+            }
+            _ => {}
+        }
+        let start = covered_end.map_or(range.start, |end| range.start.max(end));
+        if let Some(validator_count) = validator_count {
+            covered += range.end.min(validator_count).saturating_sub(start);
+            if range.end > validator_count {
+                issues.push(RangeIssue::BeyondRegistry {
+                    entry: entry.clone(),
+                    validator_count,
+                });
+            }
+        }
+        covered_end = Some(covered_end.map_or(range.end, |end| end.max(range.end)));
+    }
+
+    if let (Some(validator_count), Some(end)) = (validator_count, covered_end) {
+        if end < validator_count {
+            issues.push(RangeIssue::Gap {
+                indexes: end..validator_count,
+            });
+        }
+    }
+    if let Some(validator_count) = validator_count {
+        if covered < validator_count {
+            issues.push(RangeIssue::Uncovered {
+                count: validator_count - covered,
+                validator_count,
+            });
+        }
+    }
+
+    issues
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -109,14 +661,14 @@ mod tests {
         for input in [
             "0-10", "0..10", "0..10:", "[0..10]:", "[0..10]", "[0-10]", "(0..10)", "[0-10)",
         ] {
-            assert_eq!(parse_range(&input).unwrap(), 0..10);
+            assert_eq!(parse_range(input).unwrap(), 0..10);
         }
     }
 
     #[test]
     fn parse_range_single_test() {
         for input in ["10", "10:", " 10  ", " 10: "] {
-            assert_eq!(parse_range(&input).unwrap(), 10..11);
+            assert_eq!(parse_range(input).unwrap(), 10..11);
         }
     }
 
@@ -186,4 +738,168 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn parse_ranges_file_yaml_labels_test() {
+        assert_eq!(
+            parse_ranges_test(
+                "
+0..100:
+  entity: entityA
+  cl: lighthouse
+  el: geth
+  node: 0
+100..200:
+  entity: Gateway Fm
+  cl: lodestar
+",
+            ),
+            vec![
+                ("entity=\"Gateway Fm\" cl=lodestar".to_owned(), er(100..200)),
+                (
+                    "entity=entityA cl=lighthouse el=geth node=0".to_owned(),
+                    er(0..100)
+                ),
+            ]
+        );
+        // Keys other than LABEL_KEYS are kept in the name
+        assert_eq!(
+            parse_ranges_test("0..100:\n  team: a\n  cl: lighthouse\n"),
+            vec![("cl=lighthouse team=a".to_owned(), er(0..100))]
+        );
+        assert!(parse_ranges("0..100:\n  team a: x\n").is_err());
+    }
+
+    #[test]
+    fn parse_group_labels_test() {
+        assert_eq!(
+            parse_group_labels("entity=\"Gateway Fm\" cl=lighthouse node=0"),
+            Some([
+                "Gateway Fm".to_owned(),
+                "lighthouse".to_owned(),
+                "".to_owned(),
+                "0".to_owned()
+            ])
+        );
+        assert_eq!(parse_group_labels("Nethermind lighthouse-0"), None);
+        assert_eq!(parse_group_labels("Gateway lh + erigon"), None);
+        assert_eq!(
+            parse_group_labels("entity=a client=lighthouse"),
+            Some(["a".to_owned(), "".to_owned(), "".to_owned(), "".to_owned()])
+        );
+        // Free-form names with `=` in every token load as before
+        assert_eq!(parse_group_labels("a=b c=d"), None);
+        assert_eq!(
+            parse_ranges_test("0..100 a=b c=d"),
+            vec![("a=b c=d".to_owned(), er(0..100))]
+        );
+    }
+
+    #[test]
+    fn group_by_label_test() {
+        let groups = vec![
+            ("entity=a cl=lighthouse node=0".to_owned(), er(0..10)),
+            ("entity=a cl=teku node=1".to_owned(), er(10..20)),
+            ("entity=b cl=lighthouse".to_owned(), er(5..30)),
+            ("Nethermind lighthouse-0".to_owned(), er(30..40)),
+        ];
+        assert_eq!(
+            group_by_label(&groups, "cl").unwrap(),
+            vec![
+                ("lighthouse".to_owned(), er(0..30)),
+                ("teku".to_owned(), er(10..20)),
+            ]
+        );
+        assert_eq!(
+            group_by_label(&groups, "entity").unwrap(),
+            vec![("a".to_owned(), er(0..20)), ("b".to_owned(), er(5..30))]
+        );
+        assert!(group_by_label(&groups, "el").unwrap().is_empty());
+    }
+
+    #[test]
+    fn lint_ranges_test() {
+        let input = "
+0-500 entityA lh-0
+500-400 entityA lh-1
+600-600 entityB
+5000-6250 Gateway lh + erigon
+6150-7500 Gateway lh + nethermind
+6200-6300 Gateway lh + erigon
+no-space
+abc entityC
+7500..7600 entity=C cleint=lh
+";
+        let issues = lint_ranges(input, Some(7000));
+        let lines: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "line 8: cannot parse \"no-space\": missing group name after the range",
+                "line 9: cannot parse \"abc entityC\": Invalid range format: abc",
+                "line 3: inverted range 500..400 of entityA lh-1",
+                "line 4: empty range 600..600 of entityB",
+                "line 10: unknown label keys cleint of entity=C cleint=lh, only entity, cl, el, node are exported as labels",
+                "line 5: range 5000..6250 of Gateway lh + erigon overlaps line 6: range 6150..7500 of Gateway lh + nethermind at indexes 6150..6250",
+                "line 6: range 6150..7500 of Gateway lh + nethermind overlaps line 7: range 6200..6300 of Gateway lh + erigon at indexes 6200..6300",
+                "gap of indexes 500..5000 not in any range",
+                "line 6: range 6150..7500 of Gateway lh + nethermind has 500 indexes beyond the 7000 validators in the registry",
+                "line 10: range 7500..7600 of entity=C cleint=lh has 100 indexes beyond the 7000 validators in the registry",
+                "4500 of the 7000 validators in the registry are not in any range",
+            ]
+        );
+        assert_eq!(issues.iter().filter(|issue| issue.is_error()).count(), 6);
+
+        assert!(lint_ranges("0..10 a\n10..20 b\n", Some(20)).is_empty());
+        // Gaps before the first and after the last range, only known with the registry size
+        assert_eq!(
+            lint_ranges("100..200 a\n200..300 b\n", Some(400))
+                .iter()
+                .map(|issue| issue.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "gap of indexes 0..100 not in any range",
+                "gap of indexes 300..400 not in any range",
+                "200 of the 400 validators in the registry are not in any range",
+            ]
+        );
+        assert!(lint_ranges("100..200 a\n200..300 b\n", None).is_empty());
+        assert_eq!(
+            lint_ranges("{\"0..10\": \"a\",\n\"10..20\" \"b\"}", None)
+                .into_iter()
+                .map(|issue| issue.to_string().split(':').next().unwrap().to_owned())
+                .collect::<Vec<_>>(),
+            vec!["line 2"]
+        );
+    }
+
+    #[test]
+    fn lint_ranges_json_test() {
+        let lines = |input: &str| -> Vec<String> {
+            lint_ranges(input, None)
+                .iter()
+                .map(|issue| issue.to_string())
+                .collect()
+        };
+        // Valid JSON rejected by YAML for its duplicate keys
+        assert_eq!(
+            lines("{\"abc\": \"a\", \"abc\": \"b\"}"),
+            vec![
+                "cannot parse \"abc\": Invalid range format: abc",
+                "cannot parse \"abc\": duplicate range key",
+            ]
+        );
+        assert_eq!(
+            lines("{\"0..10\": \"a\", \"10..20\": \"b\", \"0..10\": \"c\"}"),
+            vec!["cannot parse \"0..10\": duplicate range key"]
+        );
+        assert!(lines("{\"0..10\": \"a\", \"10..20\": \"b\"}").is_empty());
+    }
+
+    #[test]
+    fn parse_ranges_json_duplicate_keys_test() {
+        let err = parse_ranges("{\"0..10\": \"a\", \"0..10\": \"b\"}").unwrap_err();
+        assert_eq!(err.to_string(), "Duplicate range key: 0..10");
+        assert!(parse_ranges("{\"abc\": \"a\"}").is_err());
+    }
 }
@@ -0,0 +1,163 @@
+use crate::metrics::{RANGES_LAST_RELOAD_TIMESTAMP, RANGES_RELOAD_SUCCESS};
+use crate::pubkeys::Groups;
+use anyhow::{anyhow, Result};
+use log::{error, info};
+use notify::{RecursiveMode, Watcher};
+use std::{
+    collections::HashSet,
+    ffi::{OsStr, OsString},
+    future::Future,
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::{mpsc, watch};
+use tokio::time;
+use url::Url;
+
+/// Wait after a file change for the rest of its events, a single write emits several
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Kubernetes ConfigMap volumes swap this symlink to a new directory on updates, the mounted
+/// files themselves are symlinks through it and emit no events
+const CONFIGMAP_DATA_LINK: &str = "..data";
+
+/// Export the result of loading the groups files
+pub fn set_reload_to_metrics(success: bool) {
+    RANGES_RELOAD_SUCCESS.set(if success { 1.0 } else { 0.0 });
+    if success {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        RANGES_LAST_RELOAD_TIMESTAMP.set(now.as_secs_f64());
+    }
+}
+
+/// Reload the groups with `load` when a local file of `sources` changes, and every
+/// `poll_interval` if any of `sources` is a URL. Changed groups are sent to `tx`, on errors the
+/// previous groups are kept.
+pub async fn reload_groups_on_change<F, Fut>(
+    sources: &[String],
+    poll_interval: Duration,
+    load: F,
+    tx: watch::Sender<Arc<Groups>>,
+) -> Result<()>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<Groups>>,
+{
+    let local_sources: Vec<&String> = sources
+        .iter()
+        .filter(|source| Url::parse(source).is_err())
+        .collect();
+    let has_urls = local_sources.len() < sources.len();
+
+    // Directories are watched, ignore the events of their other files like logs
+    let mut file_names: HashSet<OsString> = local_sources
+        .iter()
+        .filter_map(|source| Path::new(source).file_name())
+        .map(OsStr::to_owned)
+        .collect();
+    file_names.insert(OsString::from(CONFIGMAP_DATA_LINK));
+
+    let (change_tx, mut change_rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event {
+            let is_source = event.paths.iter().any(|path| {
+                path.file_name()
+                    .is_some_and(|file_name| file_names.contains(file_name))
+            });
+            if !event.kind.is_access() && is_source {
+                let _ = change_tx.send(());
+            }
+        }
+    })?;
+
+    for source in local_sources {
+        // Watch the parent directory, editors and Kubernetes ConfigMaps replace the file instead
+        // of writing to it, which drops a watch on the file itself
+        let dir = Path::new(source)
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        info!("watching {} for changes", source);
+    }
+
+    let mut poll = time::interval(poll_interval);
+    // The first tick completes immediately, groups were just loaded
+    poll.tick().await;
+
+    loop {
+        tokio::select! {
+            Some(()) = change_rx.recv() => {
+                time::sleep(DEBOUNCE).await;
+                while change_rx.try_recv().is_ok() {}
+            }
+            _ = poll.tick(), if has_urls => {}
+            else => return Err(anyhow!("no groups files to reload")),
+        }
+
+        match load().await {
+            Err(e) => {
+                error!("error reloading groups, keeping previous groups: {:?}", e);
+                set_reload_to_metrics(false);
+            }
+            Ok(groups) => {
+                set_reload_to_metrics(true);
+                if **tx.borrow() != groups {
+                    info!("groups changed, swapping");
+                    tx.send_replace(Arc::new(groups));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ranges::parse_ranges;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn reload_on_file_change() {
+        let dir = std::env::temp_dir().join(format!("ranges-reload-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ranges.txt");
+        fs::write(&path, "0..10 a\n").unwrap();
+        let path = path.to_str().unwrap().to_owned();
+
+        let loads = AtomicUsize::new(0);
+        let load = || async {
+            loads.fetch_add(1, Ordering::SeqCst);
+            Ok(Groups {
+                ranges: parse_ranges(&fs::read_to_string(&path)?)?,
+                ..Default::default()
+            })
+        };
+        let (tx, mut rx) = watch::channel(Arc::new(load().await.unwrap()));
+        let sources = vec![path.clone()];
+        let reload = reload_groups_on_change(&sources, Duration::from_secs(3600), load, tx);
+
+        let changed = async {
+            // Let the watcher start before writing
+            time::sleep(Duration::from_millis(100)).await;
+            // Other files of the directory do not reload the groups
+            fs::write(dir.join("gazer.log"), "log line\n").unwrap();
+            time::sleep(DEBOUNCE * 2).await;
+            assert_eq!(loads.load(Ordering::SeqCst), 1);
+            fs::write(&path, "0..10 a\n10..20 b\n").unwrap();
+            rx.changed().await.unwrap();
+        };
+        tokio::select! {
+            result = reload => panic!("reload returned {:?}", result),
+            _ = time::timeout(Duration::from_secs(5), changed) => {}
+        }
+        assert_eq!(rx.borrow().ranges.len(), 2);
+        assert_eq!(RANGES_RELOAD_SUCCESS.get(), 1.0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
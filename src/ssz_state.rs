@@ -1,4 +1,5 @@
-use crate::config::ConfigSpec;
+use crate::config::{ConfigSpec, ForkName};
+use crate::phase0::Phase0State;
 use anyhow::{anyhow, Context, Result};
 use byteorder::{ByteOrder, LittleEndian};
 use bytes::{Buf, Bytes};
@@ -6,12 +7,147 @@ use std::ops::Range;
 
 #[derive(Debug)]
 pub struct StatePartial {
+    pub fork: ForkName,
+    pub genesis_time: u64,
     pub slot: u64,
+    /// Epoch described by `previous_epoch_participation`, the epoch before the epoch of `slot`
+    /// unless the participation was rotated to a later epoch boundary
+    pub participation_epoch: u64,
+    pub validators: Vec<Validator>,
+    pub balances: Vec<u64>,
     pub previous_epoch_participation: Vec<u8>,
     pub current_epoch_participation: Vec<u8>,
+    pub justification_bits: u8,
+    pub previous_justified_checkpoint: Checkpoint,
+    pub current_justified_checkpoint: Checkpoint,
+    pub finalized_checkpoint: Checkpoint,
     pub inactivity_scores: Vec<u64>,
+    /// Pubkeys of the sync committee members, in committee order
+    pub current_sync_committee: Vec<BlsPubkey>,
+    pub next_sync_committee: Vec<BlsPubkey>,
+    /// Electra deposit and withdrawal queues, empty before Electra
+    pub pending_deposits: Vec<PendingDeposit>,
+    pub pending_partial_withdrawals: Vec<PendingPartialWithdrawal>,
+    pub pending_consolidations: Vec<PendingConsolidation>,
 }
 
+pub type BlsPubkey = [u8; 48];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub epoch: u64,
+    pub root: [u8; 32],
+}
+
+/// Subset of the spec `PendingDeposit` container
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingDeposit {
+    pub pubkey: BlsPubkey,
+    /// Amount in Gwei
+    pub amount: u64,
+    pub slot: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingPartialWithdrawal {
+    pub validator_index: usize,
+    /// Amount in Gwei
+    pub amount: u64,
+    pub withdrawable_epoch: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingConsolidation {
+    pub source_index: usize,
+    pub target_index: usize,
+}
+
+/// Subset of the spec `Validator` container
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Validator {
+    pub pubkey: BlsPubkey,
+    pub withdrawal_credentials: [u8; 32],
+    pub effective_balance: u64,
+    pub slashed: bool,
+    pub activation_eligibility_epoch: u64,
+    pub activation_epoch: u64,
+    pub exit_epoch: u64,
+    pub withdrawable_epoch: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidatorStatus {
+    Pending,
+    Active,
+    Exiting,
+    Exited,
+    Slashed,
+}
+
+pub const FAR_FUTURE_EPOCH: u64 = u64::MAX;
+
+// https://github.com/ethereum/consensus-specs/blob/4a27f855439c16612ab1ae3995d71bed54f979ea/specs/altair/beacon-chain.md#participation-flag-indices
+const TIMELY_SOURCE_FLAG_INDEX: u8 = 0;
+const TIMELY_TARGET_FLAG_INDEX: u8 = 1;
+const TIMELY_HEAD_FLAG_INDEX: u8 = 2;
+pub const TIMELY_SOURCE: u8 = 1 << TIMELY_SOURCE_FLAG_INDEX;
+pub const TIMELY_TARGET: u8 = 1 << TIMELY_TARGET_FLAG_INDEX;
+pub const TIMELY_HEAD: u8 = 1 << TIMELY_HEAD_FLAG_INDEX;
+
+impl ValidatorStatus {
+    pub const ALL: [ValidatorStatus; 5] = [
+        ValidatorStatus::Pending,
+        ValidatorStatus::Active,
+        ValidatorStatus::Exiting,
+        ValidatorStatus::Exited,
+        ValidatorStatus::Slashed,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValidatorStatus::Pending => "pending",
+            ValidatorStatus::Active => "active",
+            ValidatorStatus::Exiting => "exiting",
+            ValidatorStatus::Exited => "exited",
+            ValidatorStatus::Slashed => "slashed",
+        }
+    }
+}
+
+impl Validator {
+    /// Spec `is_active_validator`
+    pub fn is_active(&self, epoch: u64) -> bool {
+        self.activation_epoch <= epoch && epoch < self.exit_epoch
+    }
+
+    /// Simplified version of the beacon API validator status at `epoch`. Slashed validators are
+    /// reported as slashed regardless of their activation and exit epochs.
+    pub fn status(&self, epoch: u64) -> ValidatorStatus {
+        if self.slashed {
+            ValidatorStatus::Slashed
+        } else if epoch < self.activation_epoch {
+            ValidatorStatus::Pending
+        } else if epoch >= self.exit_epoch {
+            ValidatorStatus::Exited
+        } else if self.exit_epoch != FAR_FUTURE_EPOCH {
+            ValidatorStatus::Exiting
+        } else {
+            ValidatorStatus::Active
+        }
+    }
+}
+
+// class Validator(Container):
+//     pubkey: BLSPubkey - 48 bytes
+//     withdrawal_credentials: Bytes32 - 32 bytes
+//     effective_balance: Gwei - 8 bytes
+//     slashed: boolean - 1 byte
+//     activation_eligibility_epoch: Epoch - 8 bytes
+//     activation_epoch: Epoch - 8 bytes
+//     exit_epoch: Epoch - 8 bytes
+//     withdrawable_epoch: Epoch - 8 bytes
+const VALIDATOR_SIZE: usize = 48 + 32 + 8 + 1 + 8 + 8 + 8 + 8;
+
 // class BeaconState(Container):
 //     # Versioning
 //     genesis_time: uint64 - 8 bytes
@@ -34,99 +170,499 @@ pub struct StatePartial {
 //     randao_mixes: Vector[Bytes32, EPOCHS_PER_HISTORICAL_VECTOR] - 32*EPOCHS_PER_HISTORICAL_VECTOR
 //     # Slashings
 //     slashings: Vector[Gwei, EPOCHS_PER_SLASHINGS_VECTOR] - 8*EPOCHS_PER_SLASHINGS_VECTOR
+//     # Attestations
+//     previous_epoch_attestations: List[PendingAttestation, MAX_ATTESTATIONS * SLOTS_PER_EPOCH] - 4 bytes (offset) # [Phase0 only]
+//     current_epoch_attestations: List[PendingAttestation, MAX_ATTESTATIONS * SLOTS_PER_EPOCH] - 4 bytes (offset) # [Phase0 only]
 //     # Participation
-//     previous_epoch_participation: List[ParticipationFlags, VALIDATOR_REGISTRY_LIMIT] - 4 bytes (offset)
-//     current_epoch_participation: List[ParticipationFlags, VALIDATOR_REGISTRY_LIMIT] - 4 bytes (offset)
+//     previous_epoch_participation: List[ParticipationFlags, VALIDATOR_REGISTRY_LIMIT] - 4 bytes (offset) # [New in Altair]
+//     current_epoch_participation: List[ParticipationFlags, VALIDATOR_REGISTRY_LIMIT] - 4 bytes (offset) # [New in Altair]
 //     # Finality
 //     justification_bits: Bitvector[JUSTIFICATION_BITS_LENGTH] - 1 byte
 //     previous_justified_checkpoint: Checkpoint - 8+32 = 40 bytes
 //     current_justified_checkpoint: Checkpoint - 8+32 = 40 bytes
 //     finalized_checkpoint: Checkpoint - 8+32 = 40 bytes
 //     # Inactivity
-//     inactivity_scores: List[uint64, VALIDATOR_REGISTRY_LIMIT] - 4 bytes (offset)
+//     inactivity_scores: List[uint64, VALIDATOR_REGISTRY_LIMIT] - 4 bytes (offset) # [New in Altair]
 //     # Sync
-//     current_sync_committee: SyncCommittee  # [New in Altair]
-//     next_sync_committee: SyncCommittee  # [New in Altair]
+//     current_sync_committee: SyncCommittee - 48*SYNC_COMMITTEE_SIZE+48 bytes # [New in Altair]
+//     next_sync_committee: SyncCommittee - 48*SYNC_COMMITTEE_SIZE+48 bytes # [New in Altair]
+//     # Execution
+//     latest_execution_payload_header: ExecutionPayloadHeader - 4 bytes (offset) # [New in Bellatrix]
+//     # Withdrawals
+//     next_withdrawal_index: WithdrawalIndex - 8 bytes # [New in Capella]
+//     next_withdrawal_validator_index: ValidatorIndex - 8 bytes # [New in Capella]
+//     # Deep history valid from Capella onwards
+//     historical_summaries: List[HistoricalSummary, HISTORICAL_ROOTS_LIMIT] - 4 bytes (offset) # [New in Capella]
+//     deposit_requests_start_index: uint64 - 8 bytes # [New in Electra]
+//     deposit_balance_to_consume: Gwei - 8 bytes # [New in Electra]
+//     exit_balance_to_consume: Gwei - 8 bytes # [New in Electra]
+//     earliest_exit_epoch: Epoch - 8 bytes # [New in Electra]
+//     consolidation_balance_to_consume: Gwei - 8 bytes # [New in Electra]
+//     earliest_consolidation_epoch: Epoch - 8 bytes # [New in Electra]
+//     pending_deposits: List[PendingDeposit, PENDING_DEPOSITS_LIMIT] - 4 bytes (offset) # [New in Electra]
+//     pending_partial_withdrawals: List[PendingPartialWithdrawal, PENDING_PARTIAL_WITHDRAWALS_LIMIT] - 4 bytes (offset) # [New in Electra]
+//     pending_consolidations: List[PendingConsolidation, PENDING_CONSOLIDATIONS_LIMIT] - 4 bytes (offset) # [New in Electra]
+//
+// Deneb does not add fields to the BeaconState, only to the ExecutionPayloadHeader
+
+// class PendingDeposit(Container):
+//     pubkey: BLSPubkey - 48 bytes
+//     withdrawal_credentials: Bytes32 - 32 bytes
+//     amount: Gwei - 8 bytes
+//     signature: BLSSignature - 96 bytes
+//     slot: Slot - 8 bytes
+const PENDING_DEPOSIT_SIZE: usize = 48 + 32 + 8 + 96 + 8;
+
+// class PendingPartialWithdrawal(Container):
+//     validator_index: ValidatorIndex - 8 bytes
+//     amount: Gwei - 8 bytes
+//     withdrawable_epoch: Epoch - 8 bytes
+const PENDING_PARTIAL_WITHDRAWAL_SIZE: usize = 8 + 8 + 8;
+
+// class PendingConsolidation(Container):
+//     source_index: ValidatorIndex - 8 bytes
+//     target_index: ValidatorIndex - 8 bytes
+const PENDING_CONSOLIDATION_SIZE: usize = 8 + 8;
+
+// class SyncCommittee(Container):
+//     pubkeys: Vector[BLSPubkey, SYNC_COMMITTEE_SIZE] - 48*SYNC_COMMITTEE_SIZE
+//     aggregate_pubkey: BLSPubkey - 48 bytes
 
 // const SLOTS_PER_HISTORICAL_ROOT: usize = usize::pow(2, 13);
 // const EPOCHS_PER_HISTORICAL_VECTOR: usize = usize::pow(2, 16);
 // const EPOCHS_PER_SLASHINGS_VECTOR: usize = usize::pow(2, 13);
 
-pub fn deserialize_partial_state(config: &ConfigSpec, state: &Bytes) -> Result<StatePartial> {
-    // Const derived from config
-    let slot_offset = 8 + 32;
-    let slot = read_u64(state, slot_offset).context("slot_offset out of bounds")?;
-    let previous_epoch_participation_offset_offset = 8
-        + 32  // genesis_validators_root
-        + 8   // slot
-        + 16  // fork
-        + 112 // latest_block_header
-        + 32 * config.slots_per_historical_root // block_roots
-        + 32 * config.slots_per_historical_root // state_roots
-        + 4   // historical_roots
-        + 72  // eth1_data
-        + 4   // eth1_data_votes
-        + 8   // eth1_deposit_index
-        + 4   // validators
-        + 4   // balances
-        + 32 * config.epochs_per_historical_vector // randao_mixes
-        + 8 * config.epochs_per_slashings_vector; // slashings
-
-    let current_epoch_participation_offset_offset = previous_epoch_participation_offset_offset + 4; // previous_epoch_participation
-
-    let inactivity_scores_offset_offset = current_epoch_participation_offset_offset
-        + 4   // current_epoch_participation
-        + 1   // justification_bits
-        + 40  // previous_justified_checkpoint
-        + 40  // current_justified_checkpoint
-        + 40; // finalized_checkpoint
-
-    // Read offset values from state
-    let previous_epoch_participation_offset =
-        read_offset(state, previous_epoch_participation_offset_offset)
-            .context("previous_epoch_participation_offset_offset out of bounds")?;
-    let current_epoch_participation_offset =
-        read_offset(state, current_epoch_participation_offset_offset)
-            .context("current_epoch_participation_offset_offset out of bounds")?;
-    let inactivity_scores_offset = read_offset(state, inactivity_scores_offset_offset)
-        .context("inactivity_scores_offset_offset out of bounds")?;
-
-    // Assume well-formed state, derive validator count from previous_epoch_participation size.
-    // Altair state does not have any other variable size field after inactivity_scores, however Bellatrix state does.
-    // So infering the size of inactivity_scores from previous_epoch_participation prevents this code from having
-    // to be fork aware, for states after phase0.
-    let validator_count = current_epoch_participation_offset - previous_epoch_participation_offset;
-
-    // With offset values, read slices
-    let previous_epoch_participation = slice(
-        state,
-        previous_epoch_participation_offset
-            ..(previous_epoch_participation_offset + validator_count),
-    )
-    .context("previous_epoch_participation_offset out of bounds")?
-    .to_vec();
-    let current_epoch_participation = slice(
-        state,
-        current_epoch_participation_offset..(current_epoch_participation_offset + validator_count),
-    )
-    .context("current_epoch_participation_offset out of bounds")?
-    .to_vec();
-    let inactivity_scores = convert_u8_to_u64(
-        &slice(
-            state,
-            inactivity_scores_offset..(inactivity_scores_offset + validator_count * 8),
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StateField {
+    GenesisTime,
+    GenesisValidatorsRoot,
+    Slot,
+    Fork,
+    LatestBlockHeader,
+    BlockRoots,
+    StateRoots,
+    HistoricalRoots,
+    Eth1Data,
+    Eth1DataVotes,
+    Eth1DepositIndex,
+    Validators,
+    Balances,
+    RandaoMixes,
+    Slashings,
+    PreviousEpochAttestations,
+    CurrentEpochAttestations,
+    PreviousEpochParticipation,
+    CurrentEpochParticipation,
+    JustificationBits,
+    PreviousJustifiedCheckpoint,
+    CurrentJustifiedCheckpoint,
+    FinalizedCheckpoint,
+    InactivityScores,
+    CurrentSyncCommittee,
+    NextSyncCommittee,
+    LatestExecutionPayloadHeader,
+    NextWithdrawalIndex,
+    NextWithdrawalValidatorIndex,
+    HistoricalSummaries,
+    DepositRequestsStartIndex,
+    DepositBalanceToConsume,
+    ExitBalanceToConsume,
+    EarliestExitEpoch,
+    ConsolidationBalanceToConsume,
+    EarliestConsolidationEpoch,
+    PendingDeposits,
+    PendingPartialWithdrawals,
+    PendingConsolidations,
+}
+
+/// Size of a field in the fixed part of a container. Variable size fields take an offset of 4
+/// bytes in the fixed part, and their content follows the fixed part in field order.
+#[derive(Debug, Clone, Copy)]
+enum FieldSize {
+    Fixed(usize),
+    Variable,
+}
+
+/// Ordered fields of the BeaconState container of `fork`, see layout above
+fn state_fields(fork: ForkName, config: &ConfigSpec) -> Vec<(StateField, FieldSize)> {
+    use FieldSize::{Fixed, Variable};
+    use StateField::*;
+
+    let sync_committee_size = 48 * config.sync_committee_size + 48;
+    let mut fields = vec![
+        (GenesisTime, Fixed(8)),
+        (GenesisValidatorsRoot, Fixed(32)),
+        (Slot, Fixed(8)),
+        (Fork, Fixed(16)),
+        (LatestBlockHeader, Fixed(112)),
+        (BlockRoots, Fixed(32 * config.slots_per_historical_root)),
+        (StateRoots, Fixed(32 * config.slots_per_historical_root)),
+        (HistoricalRoots, Variable),
+        (Eth1Data, Fixed(72)),
+        (Eth1DataVotes, Variable),
+        (Eth1DepositIndex, Fixed(8)),
+        (Validators, Variable),
+        (Balances, Variable),
+        (RandaoMixes, Fixed(32 * config.epochs_per_historical_vector)),
+        (Slashings, Fixed(8 * config.epochs_per_slashings_vector)),
+    ];
+    if fork == ForkName::Phase0 {
+        fields.extend([
+            (PreviousEpochAttestations, Variable),
+            (CurrentEpochAttestations, Variable),
+        ]);
+    } else {
+        fields.extend([
+            (PreviousEpochParticipation, Variable),
+            (CurrentEpochParticipation, Variable),
+        ]);
+    }
+    fields.extend([
+        (JustificationBits, Fixed(1)),
+        (PreviousJustifiedCheckpoint, Fixed(40)),
+        (CurrentJustifiedCheckpoint, Fixed(40)),
+        (FinalizedCheckpoint, Fixed(40)),
+    ]);
+    if fork >= ForkName::Altair {
+        fields.extend([
+            (InactivityScores, Variable),
+            (CurrentSyncCommittee, Fixed(sync_committee_size)),
+            (NextSyncCommittee, Fixed(sync_committee_size)),
+        ]);
+    }
+    if fork >= ForkName::Bellatrix {
+        fields.push((LatestExecutionPayloadHeader, Variable));
+    }
+    if fork >= ForkName::Capella {
+        fields.extend([
+            (NextWithdrawalIndex, Fixed(8)),
+            (NextWithdrawalValidatorIndex, Fixed(8)),
+            (HistoricalSummaries, Variable),
+        ]);
+    }
+    if fork >= ForkName::Electra {
+        fields.extend([
+            (DepositRequestsStartIndex, Fixed(8)),
+            (DepositBalanceToConsume, Fixed(8)),
+            (ExitBalanceToConsume, Fixed(8)),
+            (EarliestExitEpoch, Fixed(8)),
+            (ConsolidationBalanceToConsume, Fixed(8)),
+            (EarliestConsolidationEpoch, Fixed(8)),
+            (PendingDeposits, Variable),
+            (PendingPartialWithdrawals, Variable),
+            (PendingConsolidations, Variable),
+        ]);
+    }
+    fields
+}
+
+/// Byte ranges of each field of a serialized container
+struct ContainerLayout {
+    buf: Bytes,
+    ranges: Vec<(StateField, Range<usize>)>,
+}
+
+impl ContainerLayout {
+    fn parse(buf: &Bytes, fields: &[(StateField, FieldSize)]) -> Result<Self> {
+        let mut ranges = Vec::with_capacity(fields.len());
+        let mut variable_fields = Vec::new();
+        let mut position = 0;
+        for (field, size) in fields {
+            match size {
+                FieldSize::Fixed(size) => {
+                    ranges.push((*field, position..position + size));
+                    position += size;
+                }
+                FieldSize::Variable => {
+                    let offset = read_offset(buf, position)
+                        .with_context(|| format!("{:?} offset out of bounds", field))?;
+                    variable_fields.push((*field, offset));
+                    position += 4;
+                }
+            }
+        }
+        if position > buf.len() {
+            return Err(anyhow!(
+                "fixed part size {} > buffer size {}",
+                position,
+                buf.len()
+            ));
+        }
+        // Variable size fields start right after the fixed part. Wrong vector lengths in the
+        // config shift the fixed part, so the first offset points elsewhere.
+        if let Some((field, first_offset)) = variable_fields.first() {
+            if *first_offset != position {
+                return Err(anyhow!(
+                    "{:?} offset {} != fixed part size {}, config does not match the state",
+                    field,
+                    first_offset,
+                    position
+                ));
+            }
+        }
+
+        // Each variable size field spans until the next one, the last until the end of buf
+        for (i, (field, start)) in variable_fields.iter().enumerate() {
+            let end = variable_fields
+                .get(i + 1)
+                .map_or(buf.len(), |(_, next_start)| *next_start);
+            if end < *start {
+                return Err(anyhow!(
+                    "{:?} offsets not monotonically increasing {}..{}",
+                    field,
+                    start,
+                    end
+                ));
+            }
+            if end > buf.len() {
+                return Err(anyhow!(
+                    "{:?} offsets {}..{} out of buffer size {}",
+                    field,
+                    start,
+                    end,
+                    buf.len()
+                ));
+            }
+            ranges.push((*field, *start..end));
+        }
+
+        Ok(ContainerLayout {
+            buf: buf.clone(),
+            ranges,
+        })
+    }
+
+    fn field(&self, field: StateField) -> Result<Bytes> {
+        let (_, range) = self
+            .ranges
+            .iter()
+            .find(|(f, _)| *f == field)
+            .ok_or_else(|| anyhow!("field {:?} not in container", field))?;
+        slice(&self.buf, range.clone())
+    }
+
+    /// Returns the bytes of a list field, checking they are a whole number of items
+    fn list(&self, field: StateField, item_size: usize) -> Result<Bytes> {
+        let buf = self.field(field)?;
+        if buf.len() % item_size != 0 {
+            return Err(anyhow!(
+                "{:?} size {} not a multiple of item size {}",
+                field,
+                buf.len(),
+                item_size
+            ));
+        }
+        Ok(buf)
+    }
+}
+
+/// Fork of the state per its `fork.current_version`. If the node announced the fork with the
+/// `Eth-Consensus-Version` header, both must agree.
+fn state_fork(config: &ConfigSpec, state: &Bytes, fork_hint: Option<ForkName>) -> Result<ForkName> {
+    // genesis_time + genesis_validators_root + slot + fork.previous_version
+    let current_version_offset = 8 + 32 + 8 + 4;
+    let current_version: [u8; 4] = slice(state, current_version_offset..current_version_offset + 4)
+        .context("fork.current_version out of bounds")?
+        .as_ref()
+        .try_into()
+        .expect("slice of 4 bytes");
+
+    match (config.fork_of_version(&current_version), fork_hint) {
+        (Some(fork), Some(fork_hint)) if fork != fork_hint => Err(anyhow!(
+            "state fork version 0x{} is {} but node announced {}",
+            hex::encode(current_version),
+            fork.as_str(),
+            fork_hint.as_str()
+        )),
+        (Some(fork), _) => Ok(fork),
+        (None, Some(fork_hint)) => Ok(fork_hint),
+        (None, None) => Err(anyhow!(
+            "unknown state fork version 0x{}",
+            hex::encode(current_version)
+        )),
+    }
+}
+
+/// Deserialize the fields of interest of a BeaconState. The fork is taken from the state
+/// `fork.current_version` if it is a fork version of `config`, and errors if `fork_hint`, i.e. the
+/// `Eth-Consensus-Version` header, disagrees. `fork_hint` is only used for unknown versions.
+pub fn deserialize_partial_state(
+    config: &ConfigSpec,
+    state: &Bytes,
+    fork_hint: Option<ForkName>,
+) -> Result<StatePartial> {
+    use StateField::*;
+
+    let fork = state_fork(config, state, fork_hint)?;
+    let layout = ContainerLayout::parse(state, &state_fields(fork, config))?;
+
+    let genesis_time = read_u64(&layout.field(GenesisTime)?, 0)?;
+    let slot = read_u64(&layout.field(Slot)?, 0)?;
+
+    // Read fixed size finality fields
+    let justification_bits = layout.field(JustificationBits)?[0];
+    let previous_justified_checkpoint =
+        read_checkpoint(&layout.field(PreviousJustifiedCheckpoint)?, 0)?;
+    let current_justified_checkpoint =
+        read_checkpoint(&layout.field(CurrentJustifiedCheckpoint)?, 0)?;
+    let finalized_checkpoint = read_checkpoint(&layout.field(FinalizedCheckpoint)?, 0)?;
+
+    // Read variable size lists
+    let validators = deserialize_validators(&layout.list(Validators, VALIDATOR_SIZE)?);
+    let balances = convert_u8_to_u64(&layout.list(Balances, 8)?);
+
+    let (
+        previous_epoch_participation,
+        current_epoch_participation,
+        inactivity_scores,
+        current_sync_committee,
+        next_sync_committee,
+    ) = if fork == ForkName::Phase0 {
+        // Phase0 has no participation flags, reconstruct them from pending attestations
+        let phase0_state = Phase0State {
+            config,
+            slot,
+            validators: &validators,
+            block_roots: &layout.field(BlockRoots)?,
+            randao_mixes: &layout.field(RandaoMixes)?,
+        };
+        let current_epoch = slot / config.slots_per_epoch;
+        let previous_epoch = current_epoch.saturating_sub(1);
+        (
+            phase0_state
+                .epoch_participation(&layout.field(PreviousEpochAttestations)?, previous_epoch)
+                .context("previous_epoch_attestations")?,
+            phase0_state
+                .epoch_participation(&layout.field(CurrentEpochAttestations)?, current_epoch)
+                .context("current_epoch_attestations")?,
+            // No inactivity leak scores nor sync committees before Altair
+            vec![0; validators.len()],
+            vec![],
+            vec![],
         )
-        .context("current_epoch_participation_offset out of bounds")?,
-    );
+    } else {
+        (
+            layout.field(PreviousEpochParticipation)?.to_vec(),
+            layout.field(CurrentEpochParticipation)?.to_vec(),
+            convert_u8_to_u64(&layout.list(InactivityScores, 8)?),
+            read_sync_committee_pubkeys(
+                &layout.field(CurrentSyncCommittee)?,
+                0,
+                config.sync_committee_size,
+            )?,
+            read_sync_committee_pubkeys(
+                &layout.field(NextSyncCommittee)?,
+                0,
+                config.sync_committee_size,
+            )?,
+        )
+    };
+
+    let (pending_deposits, pending_partial_withdrawals, pending_consolidations) =
+        if fork >= ForkName::Electra {
+            (
+                deserialize_pending_deposits(&layout.list(PendingDeposits, PENDING_DEPOSIT_SIZE)?),
+                deserialize_pending_partial_withdrawals(
+                    &layout.list(PendingPartialWithdrawals, PENDING_PARTIAL_WITHDRAWAL_SIZE)?,
+                ),
+                deserialize_pending_consolidations(
+                    &layout.list(PendingConsolidations, PENDING_CONSOLIDATION_SIZE)?,
+                ),
+            )
+        } else {
+            (vec![], vec![], vec![])
+        };
+
+    // All registry lists have one item per validator, a mismatch means a wrong layout
+    for (name, len) in [
+        ("balances", balances.len()),
+        (
+            "previous_epoch_participation",
+            previous_epoch_participation.len(),
+        ),
+        (
+            "current_epoch_participation",
+            current_epoch_participation.len(),
+        ),
+        ("inactivity_scores", inactivity_scores.len()),
+    ] {
+        if len != validators.len() {
+            return Err(anyhow!(
+                "{} len {} != validators len {}, wrong {} state layout",
+                name,
+                len,
+                validators.len(),
+                fork.as_str()
+            ));
+        }
+    }
 
     Ok(StatePartial {
+        fork,
+        genesis_time,
         slot,
+        participation_epoch: (slot / config.slots_per_epoch).saturating_sub(1),
+        validators,
+        balances,
         previous_epoch_participation,
         current_epoch_participation,
+        justification_bits,
+        previous_justified_checkpoint,
+        current_justified_checkpoint,
+        finalized_checkpoint,
         inactivity_scores,
+        current_sync_committee,
+        next_sync_committee,
+        pending_deposits,
+        pending_partial_withdrawals,
+        pending_consolidations,
     })
 }
 
+fn deserialize_validators(buf: &[u8]) -> Vec<Validator> {
+    buf.chunks_exact(VALIDATOR_SIZE)
+        .map(|v| Validator {
+            pubkey: v[0..48].try_into().expect("slice of 48 bytes"),
+            withdrawal_credentials: v[48..80].try_into().expect("slice of 32 bytes"),
+            effective_balance: LittleEndian::read_u64(&v[80..88]),
+            slashed: v[88] != 0,
+            activation_eligibility_epoch: LittleEndian::read_u64(&v[89..97]),
+            activation_epoch: LittleEndian::read_u64(&v[97..105]),
+            exit_epoch: LittleEndian::read_u64(&v[105..113]),
+            withdrawable_epoch: LittleEndian::read_u64(&v[113..121]),
+        })
+        .collect()
+}
+
+fn deserialize_pending_deposits(buf: &[u8]) -> Vec<PendingDeposit> {
+    buf.chunks_exact(PENDING_DEPOSIT_SIZE)
+        .map(|d| PendingDeposit {
+            pubkey: d[0..48].try_into().expect("slice of 48 bytes"),
+            amount: LittleEndian::read_u64(&d[80..88]),
+            slot: LittleEndian::read_u64(&d[184..192]),
+        })
+        .collect()
+}
+
+fn deserialize_pending_partial_withdrawals(buf: &[u8]) -> Vec<PendingPartialWithdrawal> {
+    buf.chunks_exact(PENDING_PARTIAL_WITHDRAWAL_SIZE)
+        .map(|w| PendingPartialWithdrawal {
+            validator_index: LittleEndian::read_u64(&w[0..8]) as usize,
+            amount: LittleEndian::read_u64(&w[8..16]),
+            withdrawable_epoch: LittleEndian::read_u64(&w[16..24]),
+        })
+        .collect()
+}
+
+fn deserialize_pending_consolidations(buf: &[u8]) -> Vec<PendingConsolidation> {
+    buf.chunks_exact(PENDING_CONSOLIDATION_SIZE)
+        .map(|c| PendingConsolidation {
+            source_index: LittleEndian::read_u64(&c[0..8]) as usize,
+            target_index: LittleEndian::read_u64(&c[8..16]) as usize,
+        })
+        .collect()
+}
+
 fn slice(buf: &Bytes, range: Range<usize>) -> Result<Bytes> {
     if range.end > buf.len() {
         return Err(anyhow!(
@@ -146,6 +682,33 @@ fn read_u64(buf: &Bytes, offset: usize) -> Result<u64> {
     Ok(slice(buf, offset..(offset + 8))?.get_u64_le())
 }
 
+fn read_root(buf: &Bytes, offset: usize) -> Result<[u8; 32]> {
+    let mut root = [0u8; 32];
+    root.copy_from_slice(&slice(buf, offset..(offset + 32))?);
+    Ok(root)
+}
+
+// class Checkpoint(Container):
+//     epoch: Epoch - 8 bytes
+//     root: Root - 32 bytes
+fn read_checkpoint(buf: &Bytes, offset: usize) -> Result<Checkpoint> {
+    Ok(Checkpoint {
+        epoch: read_u64(buf, offset)?,
+        root: read_root(buf, offset + 8)?,
+    })
+}
+
+fn read_sync_committee_pubkeys(
+    buf: &Bytes,
+    offset: usize,
+    sync_committee_size: usize,
+) -> Result<Vec<BlsPubkey>> {
+    Ok(slice(buf, offset..(offset + 48 * sync_committee_size))?
+        .chunks_exact(48)
+        .map(|pubkey| pubkey.try_into().expect("chunk of 48 bytes"))
+        .collect())
+}
+
 fn convert_u8_to_u64(input: &[u8]) -> Vec<u64> {
     let num_u64s = input.len() / 8;
     let mut output = vec![0u64; num_u64s];
@@ -185,6 +748,21 @@ mod tests {
         slots_per_historical_root: 8192,
         epochs_per_historical_vector: 65536,
         epochs_per_slashings_vector: 8192,
+        sync_committee_size: 512,
+        epochs_per_sync_committee_period: 256,
+        max_committees_per_slot: 64,
+        target_committee_size: 128,
+        shuffle_round_count: 90,
+        min_seed_lookahead: 1,
+        // Fork versions of the devnet of state_148990
+        fork_versions: [
+            None,
+            None,
+            Some([0x02, 0x00, 0x00, 0x72]),
+            Some([0x03, 0x00, 0x00, 0x72]),
+            None,
+            None,
+        ],
     };
 
     const CONFIG_MAINNET: ConfigSpec = ConfigSpec {
@@ -193,6 +771,21 @@ mod tests {
         slots_per_historical_root: 8192,
         epochs_per_historical_vector: 65536,
         epochs_per_slashings_vector: 8192,
+        sync_committee_size: 512,
+        epochs_per_sync_committee_period: 256,
+        max_committees_per_slot: 64,
+        target_committee_size: 128,
+        shuffle_round_count: 90,
+        min_seed_lookahead: 1,
+        // Fork versions of the devnet of state_devnet6
+        fork_versions: [
+            None,
+            None,
+            None,
+            Some([0x40, 0x11, 0x47, 0x89]),
+            Some([0x50, 0x11, 0x47, 0x89]),
+            None,
+        ],
     };
 
     #[test]
@@ -208,7 +801,7 @@ mod tests {
             let state_bytes = fs::read(format!("{}.ssz", filename)).unwrap();
             let state_json: StateJsonStr = serde_json::from_str(&state_json).unwrap();
             let state_buf = BytesMut::from_iter(state_bytes.iter()).freeze();
-            let state = deserialize_partial_state(&config, &state_buf).unwrap();
+            let state = deserialize_partial_state(&config, &state_buf, None).unwrap();
 
             assert_eq!(
                 state.slot,
@@ -237,4 +830,356 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn devnet_state_genesis_time() {
+        for (filename, config, genesis_time, slot) in [
+            (
+                "src/fixtures/state_148990",
+                CONFIG_GNOSIS,
+                1680004800,
+                148990,
+            ),
+            (
+                "src/fixtures/state_devnet6_genesistime-1686904523_slot-416",
+                CONFIG_MAINNET,
+                1686904523,
+                416,
+            ),
+        ] {
+            let state_bytes = fs::read(format!("{}.ssz", filename)).unwrap();
+            let state_buf = BytesMut::from_iter(state_bytes.iter()).freeze();
+
+            let state = deserialize_partial_state(&config, &state_buf, None).unwrap();
+            assert_eq!(state.genesis_time, genesis_time, "{}", filename);
+            assert_eq!(state.slot, slot, "{}", filename);
+        }
+    }
+
+    #[test]
+    fn devnet_state_fork() {
+        for (filename, config, fork) in [
+            (
+                "src/fixtures/state_148990",
+                CONFIG_GNOSIS,
+                ForkName::Capella,
+            ),
+            (
+                "src/fixtures/state_devnet6_genesistime-1686904523_slot-416",
+                CONFIG_MAINNET,
+                ForkName::Deneb,
+            ),
+        ] {
+            let state_bytes = fs::read(format!("{}.ssz", filename)).unwrap();
+            let state_buf = BytesMut::from_iter(state_bytes.iter()).freeze();
+
+            let state = deserialize_partial_state(&config, &state_buf, None).unwrap();
+            assert_eq!(state.fork, fork, "{}", filename);
+            let state = deserialize_partial_state(&config, &state_buf, Some(fork)).unwrap();
+            assert_eq!(state.fork, fork, "{}", filename);
+
+            // Layout of a different fork must not decode silently
+            assert!(
+                deserialize_partial_state(&config, &state_buf, Some(ForkName::Electra)).is_err(),
+                "{}",
+                filename
+            );
+            let unknown_versions = ConfigSpec {
+                fork_versions: [None; ForkName::ALL.len()],
+                ..config
+            };
+            assert!(
+                deserialize_partial_state(&unknown_versions, &state_buf, None).is_err(),
+                "{}",
+                filename
+            );
+            assert!(
+                deserialize_partial_state(&unknown_versions, &state_buf, Some(ForkName::Electra))
+                    .is_err(),
+                "{}",
+                filename
+            );
+            let state =
+                deserialize_partial_state(&unknown_versions, &state_buf, Some(fork)).unwrap();
+            assert_eq!(state.fork, fork, "{}", filename);
+        }
+    }
+
+    #[test]
+    fn devnet_state_registry() {
+        for (filename, config, validator_count, balance_total, effective_balance_total, first) in [
+            (
+                "src/fixtures/state_148990",
+                CONFIG_GNOSIS,
+                10000,
+                265233489986252,
+                263404000000000,
+                (
+                    "8170f2611ac4d66802c9c46399bf7e827171c10a8c54d019296f93fcccc66a3da4766a5e9791efe629719372ca7dde59",
+                    "0100000000000000000000007b380660b3e857971ffc04a7ada5ce563acf9f31",
+                ),
+            ),
+            (
+                "src/fixtures/state_devnet6_genesistime-1686904523_slot-416",
+                CONFIG_MAINNET,
+                1550,
+                49603637519524,
+                49600000000000,
+                (
+                    "971b9e7cd4962d17c955d4c0b2340e20f535a1c66e03b68292713676135d79ca8e76417166cdc3ef35bbebf3533bd3fd",
+                    "001120097fd4bd6fa9e74b713638437c92887dcced14fc7ad53e6ca00b7fdf68",
+                ),
+            ),
+        ] {
+            let state_bytes = fs::read(format!("{}.ssz", filename)).unwrap();
+            let state_buf = BytesMut::from_iter(state_bytes.iter()).freeze();
+            let state = deserialize_partial_state(&config, &state_buf, None).unwrap();
+
+            assert_eq!(state.validators.len(), validator_count, "{}", filename);
+            assert_eq!(state.balances.len(), validator_count, "{}", filename);
+            assert_eq!(
+                (
+                    hex::encode(state.validators[0].pubkey).as_str(),
+                    hex::encode(state.validators[0].withdrawal_credentials).as_str()
+                ),
+                first,
+                "first validator {}",
+                filename
+            );
+            assert_eq!(
+                state
+                    .validators
+                    .iter()
+                    .map(|v| v.effective_balance)
+                    .sum::<u64>(),
+                effective_balance_total,
+                "effective_balance {}",
+                filename
+            );
+            let epoch = state.slot / config.slots_per_epoch;
+            assert!(
+                state
+                    .validators
+                    .iter()
+                    .all(|v| v.status(epoch) == ValidatorStatus::Active),
+                "status {}",
+                filename
+            );
+            assert_eq!(
+                state.balances.iter().sum::<u64>(),
+                balance_total,
+                "balances {}",
+                filename
+            );
+        }
+    }
+
+    #[test]
+    fn devnet_state_finality() {
+        for (filename, config, justification_bits, justified_epochs, finalized) in [
+            (
+                "src/fixtures/state_148990",
+                CONFIG_GNOSIS,
+                0,
+                (5519, 5519),
+                (
+                    5473,
+                    "1c27d6511bf7651d357f00672fdc7876c77744ee1ec4fe4fb7c84e0e3dbd4ef9",
+                ),
+            ),
+            (
+                "src/fixtures/state_devnet6_genesistime-1686904523_slot-416",
+                CONFIG_MAINNET,
+                0b1111,
+                (11, 12),
+                (
+                    11,
+                    "47000cc9f0a06a7e7a478780a601a5d48b29c9af442dbb32f701564b5a3c1474",
+                ),
+            ),
+        ] {
+            let state_bytes = fs::read(format!("{}.ssz", filename)).unwrap();
+            let state_buf = BytesMut::from_iter(state_bytes.iter()).freeze();
+            let state = deserialize_partial_state(&config, &state_buf, None).unwrap();
+
+            assert_eq!(state.justification_bits, justification_bits, "{}", filename);
+            assert_eq!(
+                (
+                    state.previous_justified_checkpoint.epoch,
+                    state.current_justified_checkpoint.epoch
+                ),
+                justified_epochs,
+                "{}",
+                filename
+            );
+            assert_eq!(
+                (
+                    state.finalized_checkpoint.epoch,
+                    hex::encode(state.finalized_checkpoint.root).as_str()
+                ),
+                finalized,
+                "{}",
+                filename
+            );
+        }
+    }
+
+    #[test]
+    fn devnet_state_sync_committee() {
+        for (filename, config, current_first, next_first) in [
+            (
+                "src/fixtures/state_148990",
+                CONFIG_GNOSIS,
+                [4044, 189, 3375, 7945],
+                [2797, 4299, 1874, 2925],
+            ),
+            (
+                "src/fixtures/state_devnet6_genesistime-1686904523_slot-416",
+                CONFIG_MAINNET,
+                [325, 385, 1496, 809],
+                [325, 385, 1496, 809],
+            ),
+        ] {
+            let state_bytes = fs::read(format!("{}.ssz", filename)).unwrap();
+            let state_buf = BytesMut::from_iter(state_bytes.iter()).freeze();
+            let state = deserialize_partial_state(&config, &state_buf, None).unwrap();
+
+            let index_of = |pubkey: &BlsPubkey| {
+                state
+                    .validators
+                    .iter()
+                    .position(|v| &v.pubkey == pubkey)
+                    .unwrap()
+            };
+            assert_eq!(state.current_sync_committee.len(), 512, "{}", filename);
+            assert_eq!(state.next_sync_committee.len(), 512, "{}", filename);
+            assert_eq!(
+                state.current_sync_committee[..4]
+                    .iter()
+                    .map(index_of)
+                    .collect::<Vec<_>>(),
+                current_first,
+                "current_sync_committee {}",
+                filename
+            );
+            assert_eq!(
+                state.next_sync_committee[..4]
+                    .iter()
+                    .map(index_of)
+                    .collect::<Vec<_>>(),
+                next_first,
+                "next_sync_committee {}",
+                filename
+            );
+        }
+    }
+
+    #[test]
+    fn validator_status() {
+        let validator = Validator {
+            pubkey: [0; 48],
+            withdrawal_credentials: [0; 32],
+            effective_balance: 32_000_000_000,
+            slashed: false,
+            activation_eligibility_epoch: 5,
+            activation_epoch: 10,
+            exit_epoch: FAR_FUTURE_EPOCH,
+            withdrawable_epoch: FAR_FUTURE_EPOCH,
+        };
+        assert_eq!(validator.status(9), ValidatorStatus::Pending);
+        assert_eq!(validator.status(10), ValidatorStatus::Active);
+        assert!(!validator.is_active(9));
+        assert!(validator.is_active(10));
+
+        let exiting = Validator {
+            exit_epoch: 20,
+            withdrawable_epoch: 40,
+            ..validator
+        };
+        assert_eq!(exiting.status(19), ValidatorStatus::Exiting);
+        assert_eq!(exiting.status(20), ValidatorStatus::Exited);
+        assert!(exiting.is_active(19));
+        assert!(!exiting.is_active(20));
+
+        let slashed = Validator {
+            slashed: true,
+            ..exiting
+        };
+        assert_eq!(slashed.status(15), ValidatorStatus::Slashed);
+    }
+
+    #[test]
+    fn pending_queues() {
+        let mut deposit = vec![0u8; PENDING_DEPOSIT_SIZE];
+        deposit[0..48].copy_from_slice(&[7; 48]);
+        deposit[80..88].copy_from_slice(&1_000_000_000u64.to_le_bytes());
+        deposit[184..192].copy_from_slice(&123u64.to_le_bytes());
+        assert_eq!(
+            deserialize_pending_deposits(&[deposit.clone(), deposit].concat()),
+            vec![
+                PendingDeposit {
+                    pubkey: [7; 48],
+                    amount: 1_000_000_000,
+                    slot: 123,
+                };
+                2
+            ]
+        );
+
+        let withdrawal = [5u64, 2_000_000_000, 300]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<u8>>();
+        assert_eq!(
+            deserialize_pending_partial_withdrawals(&withdrawal),
+            vec![PendingPartialWithdrawal {
+                validator_index: 5,
+                amount: 2_000_000_000,
+                withdrawable_epoch: 300,
+            }]
+        );
+
+        let consolidation = [10u64, 20]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<u8>>();
+        assert_eq!(
+            deserialize_pending_consolidations(&consolidation),
+            vec![PendingConsolidation {
+                source_index: 10,
+                target_index: 20,
+            }]
+        );
+    }
+
+    #[test]
+    fn container_layout_offsets() {
+        use StateField::*;
+        let fields = [
+            (Slot, FieldSize::Fixed(8)),
+            (Validators, FieldSize::Variable),
+            (Balances, FieldSize::Variable),
+        ];
+        let container = |first_offset: u32, second_offset: u32| {
+            let mut buf = vec![0u8; 8];
+            buf.extend_from_slice(&first_offset.to_le_bytes());
+            buf.extend_from_slice(&second_offset.to_le_bytes());
+            buf.extend_from_slice(&[0; 8]);
+            Bytes::from(buf)
+        };
+
+        let layout = ContainerLayout::parse(&container(16, 20), &fields).unwrap();
+        assert_eq!(layout.field(Validators).unwrap().len(), 4);
+        assert_eq!(layout.field(Balances).unwrap().len(), 4);
+
+        let err = |buf| {
+            ContainerLayout::parse(&buf, &fields)
+                .err()
+                .unwrap()
+                .to_string()
+        };
+        assert!(err(container(12, 20)).contains("!= fixed part size 16"));
+        assert!(err(container(16, 12)).contains("not monotonically increasing"));
+        assert!(err(container(16, 30)).contains("out of buffer size 24"));
+    }
 }
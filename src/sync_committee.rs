@@ -0,0 +1,263 @@
+use crate::config::ConfigSpec;
+use crate::ranges::IndexGroups;
+use crate::ssz_state::{BlsPubkey, StatePartial};
+use anyhow::{anyhow, Result};
+use log::debug;
+use reqwest::{header::HeaderMap, StatusCode};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Sync committee bits included in the block at `slot`, one bit per committee position
+#[derive(Debug)]
+pub struct SyncAggregateBits {
+    pub slot: u64,
+    pub bits: Vec<bool>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncCommitteeSummary {
+    /// Count of committee positions held by the group in the current sync committee
+    pub current_members: usize,
+    /// Count of committee positions held by the group in the next sync committee
+    pub next_members: usize,
+    /// Count of (block, committee position) duties of the group in the fetched blocks
+    pub duties: usize,
+    /// Count of duties with their bit set in the block's sync aggregate
+    pub participated: usize,
+}
+
+pub type SyncCommitteeByRange = Vec<(String, SyncCommitteeSummary)>;
+
+#[derive(Deserialize)]
+struct BlockResponse {
+    data: SignedBlock,
+}
+
+#[derive(Deserialize)]
+struct SignedBlock {
+    message: Block,
+}
+
+#[derive(Deserialize)]
+struct Block {
+    slot: String,
+    body: BlockBody,
+}
+
+#[derive(Deserialize)]
+struct BlockBody {
+    sync_aggregate: SyncAggregate,
+}
+
+#[derive(Deserialize)]
+struct SyncAggregate {
+    sync_committee_bits: String,
+}
+
+/// Fetch the sync aggregate of the block at `slot`. Returns None if the slot has no block
+pub async fn fetch_sync_aggregate(
+    beacon_url: &str,
+    extra_headers: &HeaderMap,
+    slot: u64,
+) -> Result<Option<SyncAggregateBits>> {
+    let res = reqwest::Client::new()
+        .get(format!("{beacon_url}/eth/v2/beacon/blocks/{slot}"))
+        .headers(extra_headers.clone())
+        .send()
+        .await?;
+
+    // Missed slots have no block
+    if res.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !res.status().is_success() {
+        return Err(anyhow!(
+            "getBlockV2 returned not success code {}",
+            res.status().as_str()
+        ));
+    }
+
+    let block: BlockResponse = res.json().await?;
+    Ok(Some(SyncAggregateBits {
+        slot: block.data.message.slot.parse()?,
+        bits: parse_bitvector(&block.data.message.body.sync_aggregate.sync_committee_bits)?,
+    }))
+}
+
+/// Fetch the sync aggregates of all blocks in `epoch`, skipping missed slots
+pub async fn fetch_epoch_sync_aggregates(
+    config: &ConfigSpec,
+    beacon_url: &str,
+    extra_headers: &HeaderMap,
+    epoch: u64,
+) -> Result<Vec<SyncAggregateBits>> {
+    let start_slot = epoch * config.slots_per_epoch;
+    let mut aggregates = Vec::new();
+    for slot in start_slot..(start_slot + config.slots_per_epoch) {
+        match fetch_sync_aggregate(beacon_url, extra_headers, slot).await? {
+            Some(aggregate) => aggregates.push(aggregate),
+            None => debug!("no block at slot {}", slot),
+        }
+    }
+    Ok(aggregates)
+}
+
+/// Parse a hex encoded SSZ Bitvector, where bit i is at byte i / 8 and bit position i % 8
+fn parse_bitvector(hex_str: &str) -> Result<Vec<bool>> {
+    let bytes = hex::decode(hex_str.trim_start_matches("0x"))?;
+    Ok((0..bytes.len() * 8)
+        .map(|i| (bytes[i / 8] >> (i % 8)) & 1 == 1)
+        .collect())
+}
+
+fn sync_committee_period(slot: u64, config: &ConfigSpec) -> u64 {
+    slot / config.slots_per_epoch / config.epochs_per_sync_committee_period
+}
+
+/// Attribute the sync committee duties of `aggregates` to each group. Only aggregates in the same
+/// sync committee period as `state` are considered, since the state only has that committee.
+pub fn group_sync_committee_participation(
+    index_groups: &IndexGroups,
+    state: &StatePartial,
+    config: &ConfigSpec,
+    aggregates: &[SyncAggregateBits],
+) -> SyncCommitteeByRange {
+    let index_by_pubkey: HashMap<&BlsPubkey, usize> = state
+        .validators
+        .iter()
+        .enumerate()
+        .map(|(index, validator)| (&validator.pubkey, index))
+        .collect();
+    let committee_indexes = |committee: &[BlsPubkey]| -> Vec<usize> {
+        committee
+            .iter()
+            .filter_map(|pubkey| index_by_pubkey.get(pubkey).copied())
+            .collect()
+    };
+    let current_committee: Vec<Option<usize>> = state
+        .current_sync_committee
+        .iter()
+        .map(|pubkey| index_by_pubkey.get(pubkey).copied())
+        .collect();
+    let current_members = committee_indexes(&state.current_sync_committee);
+    let next_members = committee_indexes(&state.next_sync_committee);
+
+    // Aggregate duties per validator first, committees are much smaller than the registry
+    let state_period = sync_committee_period(state.slot, config);
+    let mut duties_by_index: HashMap<usize, (usize, usize)> = HashMap::new();
+    for aggregate in aggregates {
+        if sync_committee_period(aggregate.slot, config) != state_period {
+            debug!(
+                "skipping sync aggregate at slot {} from a different sync committee period",
+                aggregate.slot
+            );
+            continue;
+        }
+        for (position, index) in current_committee.iter().enumerate() {
+            if let Some(index) = index {
+                let (duties, participated) = duties_by_index.entry(*index).or_default();
+                *duties += 1;
+                if aggregate.bits.get(position) == Some(&true) {
+                    *participated += 1;
+                }
+            }
+        }
+    }
+
+    index_groups
+        .iter()
+        .map(|(range_name, indexes)| {
+            // indexes of each group are sorted
+            let in_group = |index: &usize| indexes.binary_search(index).is_ok();
+            let mut summary = SyncCommitteeSummary {
+                current_members: current_members.iter().filter(|i| in_group(i)).count(),
+                next_members: next_members.iter().filter(|i| in_group(i)).count(),
+                ..Default::default()
+            };
+            for (index, (duties, participated)) in duties_by_index.iter() {
+                if in_group(index) {
+                    summary.duties += duties;
+                    summary.participated += participated;
+                }
+            }
+            (range_name.clone(), summary)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ForkName;
+    use crate::ssz_state::deserialize_partial_state;
+    use bytes::BytesMut;
+    use std::fs;
+
+    const CONFIG_MAINNET: ConfigSpec = ConfigSpec {
+        seconds_per_slot: 12,
+        slots_per_epoch: 32,
+        slots_per_historical_root: 8192,
+        epochs_per_historical_vector: 65536,
+        epochs_per_slashings_vector: 8192,
+        sync_committee_size: 512,
+        epochs_per_sync_committee_period: 256,
+        max_committees_per_slot: 64,
+        target_committee_size: 128,
+        shuffle_round_count: 90,
+        min_seed_lookahead: 1,
+        fork_versions: [None; ForkName::ALL.len()],
+    };
+
+    #[test]
+    fn parse_bitvector_test() {
+        assert_eq!(
+            parse_bitvector("0x0580").unwrap(),
+            vec![
+                true, false, true, false, false, false, false, false, //
+                false, false, false, false, false, false, false, true
+            ]
+        );
+    }
+
+    #[test]
+    fn group_sync_committee_participation_test() {
+        let state_bytes =
+            fs::read("src/fixtures/state_devnet6_genesistime-1686904523_slot-416.ssz").unwrap();
+        let state_buf = BytesMut::from_iter(state_bytes.iter()).freeze();
+        let state =
+            deserialize_partial_state(&CONFIG_MAINNET, &state_buf, Some(ForkName::Deneb)).unwrap();
+
+        // First committee position is index 325, second is index 385
+        let index_groups: IndexGroups = vec![
+            ("a".to_owned(), vec![325]),
+            ("b".to_owned(), vec![385]),
+            ("all".to_owned(), (0..1550).collect()),
+        ];
+        let mut bits = vec![true; 512];
+        bits[1] = false;
+        let aggregates = [
+            SyncAggregateBits {
+                slot: 415,
+                bits: bits.clone(),
+            },
+            SyncAggregateBits { slot: 416, bits },
+        ];
+
+        let summaries =
+            group_sync_committee_participation(&index_groups, &state, &CONFIG_MAINNET, &aggregates);
+        let by_name = |name: &str| &summaries.iter().find(|(n, _)| n == name).unwrap().1;
+
+        assert_eq!(by_name("a").duties, 2 * by_name("a").current_members);
+        assert_eq!(by_name("a").participated, by_name("a").duties);
+        assert_eq!(by_name("b").participated, by_name("b").duties - 2);
+        assert_eq!(
+            by_name("all"),
+            &SyncCommitteeSummary {
+                current_members: 512,
+                next_members: 512,
+                duties: 2 * 512,
+                participated: 2 * 512 - 2,
+            }
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::pubkeys::merge_group;
+use crate::ranges::IndexGroups;
+use crate::ssz_state::Validator;
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+
+pub type ExecutionAddress = [u8; 20];
+
+/// Execution address of 0x01 and 0x02 withdrawal credentials, None for BLS credentials
+pub fn withdrawal_address(credentials: &[u8; 32]) -> Option<ExecutionAddress> {
+    match credentials[0] {
+        0x01 | 0x02 => Some(credentials[12..32].try_into().expect("slice of 20 bytes")),
+        _ => None,
+    }
+}
+
+/// Derives groups from the withdrawal address of each validator in the state
+#[derive(Debug, Default, PartialEq)]
+pub struct WithdrawalGrouping {
+    /// Group names of known addresses, multiple addresses may share a name
+    pub names: HashMap<ExecutionAddress, String>,
+    /// Group each other address with at least this many validators, named by its address
+    pub threshold: Option<usize>,
+}
+
+impl WithdrawalGrouping {
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.threshold.is_none()
+    }
+
+    /// Index groups by withdrawal address, in order of their first validator index
+    pub fn group(&self, validators: &[Validator]) -> IndexGroups {
+        if self.is_empty() {
+            return vec![];
+        }
+
+        let mut by_address: Vec<(ExecutionAddress, Vec<usize>)> = Vec::new();
+        let mut position_by_address = HashMap::new();
+        for (index, validator) in validators.iter().enumerate() {
+            if let Some(address) = withdrawal_address(&validator.withdrawal_credentials) {
+                let position = *position_by_address.entry(address).or_insert_with(|| {
+                    by_address.push((address, vec![]));
+                    by_address.len() - 1
+                });
+                by_address[position].1.push(index);
+            }
+        }
+
+        let mut groups: IndexGroups = Vec::new();
+        for (address, indexes) in by_address {
+            let name = match (self.names.get(&address), self.threshold) {
+                (Some(name), _) => name.clone(),
+                (None, Some(threshold)) if indexes.len() >= threshold => {
+                    format!("0x{}", hex::encode(address))
+                }
+                _ => continue,
+            };
+            merge_group(&mut groups, name, indexes);
+        }
+        groups
+    }
+}
+
+/// Parse a file mapping withdrawal addresses to group names, as YAML or JSON
+/// ```yaml
+/// "0x388c818ca8b9251b393131c08a736a67ccb19297": entityA
+/// ```
+/// Otherwise as TXT with an address and its group name per line: `0x388c...9297 entityA`
+pub fn parse_withdrawal_names(input: &str) -> Result<HashMap<ExecutionAddress, String>> {
+    let entries: Vec<(String, String)> =
+        match serde_yaml::from_str::<HashMap<String, String>>(input) {
+            Ok(entries) => entries.into_iter().collect(),
+            Err(_) => input
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(|line| {
+                    line.split_once(char::is_whitespace)
+                        .map(|(address, name)| (address.to_owned(), name.trim().to_owned()))
+                        .ok_or_else(|| anyhow!("Invalid withdrawal address line: {}", line))
+                })
+                .collect::<Result<_>>()?,
+        };
+
+    entries
+        .into_iter()
+        .map(|(address_str, name)| Ok((parse_address(&address_str)?, name)))
+        .collect()
+}
+
+fn parse_address(address_str: &str) -> Result<ExecutionAddress> {
+    let bytes = hex::decode(address_str.trim().trim_start_matches("0x"))
+        .with_context(|| format!("Invalid address hex: {}", address_str))?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow!("Address not 20 bytes: {}", address_str))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssz_state::FAR_FUTURE_EPOCH;
+
+    fn validator(prefix: u8, address_byte: u8) -> Validator {
+        let mut withdrawal_credentials = [0; 32];
+        withdrawal_credentials[0] = prefix;
+        withdrawal_credentials[12..].copy_from_slice(&[address_byte; 20]);
+        Validator {
+            pubkey: [0; 48],
+            withdrawal_credentials,
+            effective_balance: 32_000_000_000,
+            slashed: false,
+            activation_eligibility_epoch: 0,
+            activation_epoch: 0,
+            exit_epoch: FAR_FUTURE_EPOCH,
+            withdrawable_epoch: FAR_FUTURE_EPOCH,
+        }
+    }
+
+    #[test]
+    fn group_by_withdrawal_address() {
+        let validators = vec![
+            validator(0x01, 0xaa),
+            validator(0x00, 0xaa),
+            validator(0x02, 0xbb),
+            validator(0x01, 0xaa),
+            validator(0x01, 0xcc),
+            validator(0x02, 0xdd),
+        ];
+        let names = parse_withdrawal_names(&format!(
+            "0x{} entity A\n0x{} entity A\n",
+            hex::encode([0xcc; 20]),
+            hex::encode([0xdd; 20])
+        ))
+        .unwrap();
+
+        let grouping = WithdrawalGrouping {
+            names: names.clone(),
+            threshold: None,
+        };
+        assert_eq!(
+            grouping.group(&validators),
+            vec![("entity A".to_owned(), vec![4, 5])]
+        );
+
+        let grouping = WithdrawalGrouping {
+            names,
+            threshold: Some(2),
+        };
+        assert_eq!(
+            grouping.group(&validators),
+            vec![
+                (format!("0x{}", hex::encode([0xaa; 20])), vec![0, 3]),
+                ("entity A".to_owned(), vec![4, 5]),
+            ]
+        );
+
+        assert!(WithdrawalGrouping::default().group(&validators).is_empty());
+    }
+
+    #[test]
+    fn parse_withdrawal_names_yaml() {
+        let names =
+            parse_withdrawal_names("\"0x388c818ca8b9251b393131c08a736a67ccb19297\": entityA\n")
+                .unwrap();
+        assert_eq!(names.values().collect::<Vec<_>>(), vec!["entityA"]);
+        assert!(parse_withdrawal_names("0x1234: entityA").is_err());
+    }
+}